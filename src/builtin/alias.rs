@@ -0,0 +1,151 @@
+use completion;
+use super::*;
+
+pub(super) struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "alias [-p] [name[=value] ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Define the aliases, or print them in a form reusable as input."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(alias(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Unalias;
+
+impl Builtin for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "unalias [-a] name [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove the aliases, or all of them if -a is given."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(unalias(args, rush, stdio), stdio)
+    }
+}
+
+/// `alias [-p] [name[=value] ...]`
+///
+/// Without operands or with `-p`, all aliases are printed in a form reusable as input.
+fn alias(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let mut print = false;
+    let mut operands = &args[1..];
+    while let Some(option) = operands.first() {
+        match option.as_ref() {
+            "--" => {
+                operands = &operands[1..];
+                break;
+            }
+            "-p" => print = true,
+            option if option.starts_with('-') && option != "-" => {
+                return Err(format!(
+                    "alias: {}: invalid option\nalias: usage: alias [-p] [name[=value] ...]",
+                    option
+                ))
+            }
+            _ => break,
+        }
+        operands = &operands[1..];
+    }
+
+    let mut aliases = rush.params.aliases.borrow_mut();
+    if print || operands.is_empty() {
+        let mut names: Vec<_> = aliases.keys().collect();
+        names.sort();
+        for name in names {
+            writeln!(stdio.stdout, "alias {}={}", name, completion::single_quote(&aliases[name]));
+        }
+    }
+
+    let mut status = 0;
+    for operand in operands {
+        match operand.split_once('=') {
+            Some((name, _)) if !is_name(name) => {
+                stdio.error(format_args!("alias: {}: invalid alias name", name));
+                status = 1;
+            }
+            Some((name, value)) => {
+                aliases.insert(name.to_owned(), value.to_owned());
+            }
+            None => match aliases.get(operand) {
+                Some(value) => {
+                    writeln!(stdio.stdout, "alias {}={}", operand, completion::single_quote(value))
+                }
+                None => {
+                    stdio.error(format_args!("alias: {}: not found", operand));
+                    status = 1;
+                }
+            },
+        }
+    }
+
+    Ok(status)
+}
+
+/// `unalias -a` or `unalias name...`
+fn unalias(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let mut aliases = rush.params.aliases.borrow_mut();
+    let operands = match args.get(1).map(String::as_str) {
+        Some("-a") => {
+            aliases.clear();
+            return Ok(0);
+        }
+        Some("--") => &args[2..],
+        _ => &args[1..],
+    };
+    if operands.is_empty() {
+        return Err(String::from(
+            "unalias: usage: unalias [-a] name [name ...]",
+        ));
+    }
+
+    let mut status = 0;
+    for name in operands {
+        if aliases.remove(name).is_none() {
+            stdio.error(format_args!("unalias: {}: not found", name));
+            status = 1;
+        }
+    }
+
+    Ok(status)
+}
+
+/// Whether `name` can be an alias, i.e. a word with no quotes, parameters or operators.
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "'\"\\$`=|&;<>()".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_name_test() {
+        assert!(is_name("ll"));
+        assert!(is_name("git-st"));
+        assert!(!is_name(""));
+        assert!(!is_name("a b"));
+        assert!(!is_name("$x"));
+        assert!(!is_name("a|b"));
+    }
+}
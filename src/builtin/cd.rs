@@ -1,16 +1,223 @@
-use std::env;
-use std::path;
+use std::{env, io};
+use std::path::{Component, Path, PathBuf};
 
 use super::*;
 
-pub(super) fn cd(args: &[String]) -> Result<()> {
-    let target = if args.len() == 1 {
-        env::home_dir().expect("Could not get your home directory")
+pub(super) struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "cd [-L|-P] [dir]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Change the current directory to dir, $HOME by default, or $OLDPWD if dir is -.\n\
+         A relative dir is searched in $CDPATH. -P resolves symbolic links."
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(cd(args, stdio), stdio)
+    }
+}
+
+pub(super) struct Pwd;
+
+impl Builtin for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "pwd [-L|-P]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the current directory, with symbolic links resolved if -P is given."
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(pwd(args, stdio), stdio)
+    }
+}
+
+/// `cd [-L|-P] [dir]`, or `cd [-L|-P] -` to go back to `$OLDPWD`
+///
+/// Without `-P`, `..` in `dir` is resolved logically, i.e. removes the last component of `$PWD`
+/// even if it is a symbolic link.
+/// A relative `dir` not starting with `.` or `..` is searched in the directories of `$CDPATH`.
+fn cd(args: &[String], stdio: &mut Stdio) -> Result<i32> {
+    let mut physical = false;
+    let mut operands = args[1..].iter().peekable();
+    while let Some(option) = operands.peek() {
+        match option.as_ref() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                operands.next();
+                break;
+            }
+            _ => break,
+        }
+        operands.next();
+    }
+    let operands: Vec<_> = operands.collect();
+    if operands.len() > 1 {
+        return Err(String::from("cd: too many arguments"));
+    }
+
+    let (dir, mut print) = match operands.first().map(|dir| dir.as_str()) {
+        None => (variable("HOME")?, false),
+        Some("-") => (variable("OLDPWD")?, true),
+        Some(dir) => (dir.to_owned(), false),
+    };
+
+    let target = match search_cdpath(&dir) {
+        Some(target) => {
+            print = true;
+            target
+        }
+        None => PathBuf::from(&dir),
+    };
+
+    let new_pwd = set_dir(&target, physical).map_err(|e| format!("cd: {}: {}", dir, e))?;
+    if print {
+        writeln!(stdio.stdout, "{}", new_pwd.display());
+    }
+
+    Ok(0)
+}
+
+/// `pwd [-L|-P]`
+///
+/// With `-P`, the physical current directory is printed, with symbolic links resolved.
+fn pwd(args: &[String], stdio: &mut Stdio) -> Result<i32> {
+    let mut physical = false;
+    for arg in &args[1..] {
+        match arg.as_ref() {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => break,
+            _ if arg.starts_with('-') => {
+                return Err(format!("pwd: {}: invalid option\npwd: usage: pwd [-LP]", arg))
+            }
+            _ => return Err(String::from("pwd: too many arguments")),
+        }
+    }
+
+    let dir = if physical {
+        env::current_dir()
     } else {
-        path::Path::new(&args[1]).to_path_buf()
+        Ok(logical_dir())
+    };
+    let dir = dir.map_err(|e| format!("pwd: error retrieving current directory: {}", e))?;
+    writeln!(stdio.stdout, "{}", dir.display());
+    Ok(0)
+}
+
+/// Change the current directory to `target` and update `$PWD` and `$OLDPWD`.
+/// Returns the new logical current directory.
+pub(super) fn set_dir(target: &Path, physical: bool) -> io::Result<PathBuf> {
+    let old_pwd = logical_dir();
+    let new_pwd = change_dir(target, &old_pwd, physical)?;
+    env::set_var("OLDPWD", &old_pwd);
+    env::set_var("PWD", &new_pwd);
+    Ok(new_pwd)
+}
+
+/// Set `$PWD` to the current directory, unless it already refers to the current directory
+/// through symbolic links.
+pub(super) fn init_pwd() {
+    let current = match env::current_dir() {
+        Ok(current) => current,
+        Err(_) => return,
     };
+    let refers_current = env::var_os("PWD").is_some_and(|pwd| {
+        let pwd = Path::new(&pwd);
+        pwd.is_absolute() && pwd.canonicalize().is_ok_and(|pwd| pwd == current)
+    });
+    if !refers_current {
+        env::set_var("PWD", current);
+    }
+}
+
+/// Value of the variable `name`, which must be set and non-empty.
+fn variable(name: &str) -> Result<String> {
+    env::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| format!("cd: {} not set", name))
+}
+
+/// The logical current directory, `$PWD` if it is absolute, or the physical one.
+pub(super) fn logical_dir() -> PathBuf {
+    match env::var_os("PWD") {
+        Some(ref pwd) if Path::new(pwd).is_absolute() => PathBuf::from(pwd),
+        _ => env::current_dir().unwrap_or_default(),
+    }
+}
+
+/// Find `dir` in the directories of `$CDPATH`, where an empty entry is the current directory.
+/// Returns `None` if not searched or not found in a non-empty entry.
+fn search_cdpath(dir: &str) -> Option<PathBuf> {
+    let path = Path::new(dir);
+    match path.components().next() {
+        Some(Component::Normal(_)) => {}
+        _ => return None,
+    }
+
+    let cdpath = env::var_os("CDPATH")?;
+    for base in env::split_paths(&cdpath) {
+        if base.as_os_str().is_empty() {
+            if path.is_dir() {
+                return None;
+            }
+        } else if base.join(path).is_dir() {
+            return Some(base.join(path));
+        }
+    }
+    None
+}
+
+/// Change the current directory to `target`, relative to `pwd`.
+/// Returns the new logical current directory.
+fn change_dir(target: &Path, pwd: &Path, physical: bool) -> io::Result<PathBuf> {
+    if physical {
+        env::set_current_dir(target)?;
+        return env::current_dir();
+    }
+
+    let logical = normalize(&pwd.join(target));
+    env::set_current_dir(&logical)?;
+    Ok(logical)
+}
+
+/// Remove `.` and resolve `..` lexically in the absolute `path`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    env::set_current_dir(&target).map_err(|_| {
-        Error::Builtin(format!("cd: No such file or directory: {:?}", target))
-    })
+    #[test]
+    fn normalize_test() {
+        assert_eq!(normalize(Path::new("/a/./b/../c/")), PathBuf::from("/a/c"));
+        assert_eq!(normalize(Path::new("/a/../../b")), PathBuf::from("/b"));
+        assert_eq!(normalize(Path::new("/")), PathBuf::from("/"));
+    }
 }
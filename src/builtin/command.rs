@@ -0,0 +1,431 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use completion::{self, single_quote};
+use job::process::{Input, Output, Process};
+use super::*;
+
+/// What a command name refers to.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CommandType {
+    /// An alias, with its value.
+    Alias(String),
+    /// A reserved word of the grammar, such as `[[`.
+    Keyword,
+    Builtin,
+    /// An executable file, found in `$PATH` if the name has no `/`.
+    File(PathBuf),
+}
+
+impl CommandType {
+    /// The word printed by `type -t`.
+    fn type_name(&self) -> &'static str {
+        match *self {
+            CommandType::Alias(_) => "alias",
+            CommandType::Keyword => "keyword",
+            CommandType::Builtin => "builtin",
+            CommandType::File(_) => "file",
+        }
+    }
+
+    /// Describe `name` of this type, as `type` does.
+    fn describe(&self, name: &str) -> String {
+        match *self {
+            CommandType::Alias(ref value) => format!("{} is aliased to `{}'", name, value),
+            CommandType::Keyword => format!("{} is a shell keyword", name),
+            CommandType::Builtin => format!("{} is a shell builtin", name),
+            CommandType::File(ref path) => format!("{} is {}", name, path.display()),
+        }
+    }
+}
+
+/// Reserved words of the grammar: `[[` starts a conditional command, which `]]` ends.
+const KEYWORDS: &[&str] = &["[[", "]]"];
+
+/// Look up `name` in the order the shell does when it runs a command: aliases, keywords,
+/// builtins, then executables remembered by `hash` or found in `$PATH`.
+/// Returns all the matches if `all`, or the first one otherwise.
+pub(crate) fn lookup(name: &str, all: bool, rush: &Rush) -> Vec<CommandType> {
+    let mut types = vec![];
+    if let Some(value) = rush.params.aliases.borrow().get(name) {
+        types.push(CommandType::Alias(value.clone()));
+    }
+    if KEYWORDS.contains(&name) {
+        types.push(CommandType::Keyword);
+    }
+    if rush.builtins.contains(name) {
+        types.push(CommandType::Builtin);
+    }
+    if !all && !types.is_empty() {
+        types.truncate(1);
+        return types;
+    }
+
+    if name.contains('/') {
+        if completion::is_executable(Path::new(name)) {
+            types.push(CommandType::File(PathBuf::from(name)));
+        }
+    } else if let (Some(path), false) = (rush.hash.get(name), all) {
+        types.push(CommandType::File(path.to_owned()));
+    } else if !name.is_empty() {
+        let path = env::var_os("PATH").unwrap_or_default();
+        for dir in env::split_paths(&path) {
+            let path = dir.join(name);
+            if completion::is_executable(&path) {
+                types.push(CommandType::File(path));
+                if !all {
+                    break;
+                }
+            }
+        }
+    }
+
+    types
+}
+
+pub(super) struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "help [-s] [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Describe the builtins, or list their synopses if no name is given. -s prints the\n\
+         synopses only."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(help(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Type;
+
+impl Builtin for Type {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "type [-apt] name [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Describe how each name would be run as a command. -a shows all the matches, -p the\n\
+         path of an executable file, and -t one of alias, keyword, builtin or file."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(type_(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Command;
+
+impl Builtin for Command {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "command [-v|-V] name [args ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run the command, ignoring aliases. -v prints how the name would be run, and -V\n\
+         describes it as type does."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(command(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct BuiltinCommand;
+
+impl Builtin for BuiltinCommand {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "builtin name [args ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run the builtin, even if an alias of the same name is defined."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        builtin(args, rush, stdio)
+    }
+}
+
+pub(super) struct Which;
+
+impl Builtin for Which {
+    fn name(&self) -> &'static str {
+        "which"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "which [-a] name [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the path of the executable file each name would run, ignoring aliases and\n\
+         builtins. -a prints all the matches in $PATH."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(which(args, rush, stdio), stdio)
+    }
+}
+
+/// `help [-s] [name ...]`
+///
+/// Without names, the synopses of all the builtins are printed.
+fn help(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (short, names) = match args.get(1).map(String::as_str) {
+        Some("-s") => (true, &args[2..]),
+        Some("--") => (false, &args[2..]),
+        _ => (false, &args[1..]),
+    };
+
+    if names.is_empty() {
+        for name in rush.builtins.names() {
+            writeln!(stdio.stdout, "{}", rush.builtins.get(name).unwrap().synopsis());
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for name in names {
+        match rush.builtins.get(name) {
+            Some(builtin) if short => writeln!(stdio.stdout, "{}: {}", name, builtin.synopsis()),
+            Some(builtin) => print_help(builtin, stdio),
+            None => {
+                stdio.error(format_args!("help: no help topics match `{}'", name));
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// `type [-apt] name...`
+///
+/// `-a` prints all the matches, `-p` the path of an executable, and `-t` the type only.
+fn type_(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (mut all, mut path, mut type_only) = (false, false, false);
+    let mut names = &args[1..];
+    while let Some(option) = names.first() {
+        if option == "--" {
+            names = &names[1..];
+            break;
+        }
+        let flags = match option.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => flags,
+            _ => break,
+        };
+        for flag in flags.chars() {
+            match flag {
+                'a' => all = true,
+                'p' => path = true,
+                't' => type_only = true,
+                _ => {
+                    return Err(format!(
+                        "type: -{}: invalid option\ntype: usage: type [-apt] name [name ...]",
+                        flag
+                    ))
+                }
+            }
+        }
+        names = &names[1..];
+    }
+
+    let mut status = 0;
+    for name in names {
+        let types = lookup(name, all, rush);
+        if types.is_empty() {
+            if !type_only && !path {
+                stdio.error(format_args!("type: {}: not found", name));
+            }
+            status = 1;
+        }
+        for found in types {
+            match found {
+                CommandType::File(ref file) if path => writeln!(stdio.stdout, "{}", file.display()),
+                _ if path => {}
+                _ if type_only => writeln!(stdio.stdout, "{}", found.type_name()),
+                _ => writeln!(stdio.stdout, "{}", found.describe(name)),
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// `command [-v|-V] name [args...]`
+///
+/// Runs `name` as a builtin or an executable, ignoring aliases, or with `-v` or `-V`, describes
+/// it briefly or as `type` does.
+fn command(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (describe, verbose, operands) = match args.get(1).map(String::as_str) {
+        Some("-v") => (true, false, &args[2..]),
+        Some("-V") => (true, true, &args[2..]),
+        Some("--") => (false, false, &args[2..]),
+        Some(option) if option.starts_with('-') && option != "-" => {
+            return Err(format!(
+                "command: {}: invalid option\ncommand: usage: command [-v|-V] name [args ...]",
+                option
+            ))
+        }
+        _ => (false, false, &args[1..]),
+    };
+
+    if !describe {
+        if operands.is_empty() {
+            return Ok(0);
+        }
+        let process = Process::new(operands.to_vec(), Input::Inherit, Output::Inherit);
+        return match process.run(rush) {
+            Ok(status) => Ok(Rush::status_code(status)),
+            Err(e) => {
+                stdio.error(&e);
+                Ok(Rush::error_status(&e))
+            }
+        };
+    }
+
+    let mut status = 0;
+    for name in operands {
+        match lookup(name, false, rush).pop() {
+            Some(ref found) if verbose => writeln!(stdio.stdout, "{}", found.describe(name)),
+            Some(CommandType::Alias(value)) => {
+                writeln!(stdio.stdout, "alias {}={}", name, single_quote(&value))
+            }
+            Some(CommandType::File(path)) => writeln!(stdio.stdout, "{}", path.display()),
+            Some(_) => writeln!(stdio.stdout, "{}", name),
+            None => {
+                if verbose {
+                    stdio.error(format_args!("command: {}: not found", name));
+                }
+                status = 1;
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// `builtin name [args...]`
+///
+/// Runs `name` as a builtin, even if an alias of the same name exists.
+fn builtin(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> ExitStatus {
+    let name = match args.get(1) {
+        Some(name) => name,
+        None => return ExitStatus::SUCCESS,
+    };
+
+    let builtins = Rc::clone(&rush.builtins);
+    match builtins.get(name) {
+        Some(builtin) => run(builtin, rush, &args[1..], stdio),
+        None => {
+            stdio.error(format_args!("builtin: {}: not a shell builtin", name));
+            ExitStatus::FAILURE
+        }
+    }
+}
+
+/// `which [-a] name...`
+///
+/// Prints the executable files found in `$PATH`, as the external `which` does, so that it also
+/// works where none is installed.
+fn which(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (all, names) = match args.get(1).map(String::as_str) {
+        Some("-a") => (true, &args[2..]),
+        Some("--") => (false, &args[2..]),
+        Some(option) if option.starts_with('-') && option != "-" => {
+            return Err(format!(
+                "which: {}: invalid option\nwhich: usage: which [-a] name ...",
+                option
+            ))
+        }
+        _ => (false, &args[1..]),
+    };
+
+    let mut status = 0;
+    for name in names {
+        let files: Vec<_> = lookup(name, true, rush)
+            .into_iter()
+            .filter_map(|found| match found {
+                CommandType::File(path) => Some(path),
+                _ => None,
+            })
+            .take(if all { usize::MAX } else { 1 })
+            .collect();
+        if files.is_empty() {
+            status = 1;
+        }
+        for file in files {
+            writeln!(stdio.stdout, "{}", file.display());
+        }
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn lookup_test() {
+        let rush = Rush::with_command("", String::from("rush"), vec![]);
+        rush.params
+            .aliases
+            .borrow_mut()
+            .insert(String::from("cd"), String::from("cd ~"));
+
+        assert_eq!(
+            lookup("cd", false, &rush),
+            vec![CommandType::Alias(String::from("cd ~"))]
+        );
+        assert_eq!(
+            lookup("cd", true, &rush)[..2],
+            [CommandType::Alias(String::from("cd ~")), CommandType::Builtin]
+        );
+        assert_eq!(lookup("[[", false, &rush), vec![CommandType::Keyword]);
+        assert_eq!(lookup("no-such-command-rush", true, &rush), vec![]);
+        assert_eq!(lookup("./src", false, &rush), vec![]);
+    }
+
+    #[test]
+    fn which_test() {
+        let mut rush = Rush::with_command("", String::from("rush"), vec![]);
+        let which = |args: &[&str], rush: &mut Rush| {
+            let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+            let (mut out, mut err) = (vec![], io::sink());
+            let status = {
+                let mut stdio = Stdio::new(0, &mut out, &mut err, None);
+                super::which(&args, rush, &mut stdio)
+            };
+            (status, String::from_utf8(out).unwrap())
+        };
+
+        let (status, out) = which(&["which", "sh"], &mut rush);
+        assert_eq!(status, Ok(0));
+        assert!(out.ends_with("/sh\n") && out.lines().count() == 1);
+        assert_eq!(which(&["which", "cd"], &mut rush), (Ok(1), String::new()));
+        assert_eq!(
+            which(&["which", "-x"], &mut rush).0,
+            Err(String::from("which: -x: invalid option\nwhich: usage: which [-a] name ..."))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::{slice, str};
+
+use completion::{Action, Spec};
+use super::*;
+
+const OPTIONS: &str = "[-fdcvuba] [-o default] [-A action] [-W wordlist] [-C command]";
+
+/// Options of `complete` and `compgen`.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    spec: Spec,
+    /// `-p`
+    print: bool,
+    /// `-r`
+    remove: bool,
+    operands: Vec<String>,
+}
+
+pub(super) struct Complete;
+
+impl Builtin for Complete {
+    fn name(&self) -> &'static str {
+        "complete"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "complete [-fdcvuba] [-o default] [-A action] [-W wordlist] [-C command] name..."
+    }
+
+    fn help(&self) -> &'static str {
+        "Specify how the arguments of the commands are completed. -p prints the specs and -r\n\
+         removes them."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(complete(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Compgen;
+
+impl Builtin for Compgen {
+    fn name(&self) -> &'static str {
+        "compgen"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "compgen [-fdcvuba] [-o default] [-A action] [-W wordlist] [-C command] [word]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the completions of word generated by the options."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(compgen(args, rush, stdio), stdio)
+    }
+}
+
+/// `complete [-fdcvuba] [-o default] [-A action] [-W wordlist] [-C command] name...`,
+/// `complete -p [name...]`, or `complete -r [name...]`
+fn complete(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let options = parse_options(args)?;
+    let mut specs = rush.completion.borrow_mut();
+
+    if options.remove {
+        if options.operands.is_empty() {
+            specs.clear();
+        }
+        for name in &options.operands {
+            specs.remove(name);
+        }
+    } else if options.print || options.operands.is_empty() {
+        let mut names: Vec<_> = if options.operands.is_empty() {
+            specs.keys().cloned().collect()
+        } else {
+            options.operands
+        };
+        names.sort();
+
+        let mut status = 0;
+        for name in names {
+            match specs.get(&name) {
+                Some(spec) if spec.to_string().is_empty() => {
+                    writeln!(stdio.stdout, "complete {}", name)
+                }
+                Some(spec) => writeln!(stdio.stdout, "complete {} {}", spec, name),
+                None => {
+                    stdio.error(format_args!("complete: {}: no completion specification", name));
+                    status = 1;
+                }
+            }
+        }
+        return Ok(status);
+    } else {
+        for name in options.operands {
+            specs.insert(name, options.spec.clone());
+        }
+    }
+
+    Ok(0)
+}
+
+/// `compgen [-fdcvuba] [-o default] [-A action] [-W wordlist] [-C command] [word]`
+fn compgen(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let options = parse_options(args)?;
+    if options.print || options.remove || options.operands.len() > 1 {
+        return Err(usage("compgen"));
+    }
+
+    let word = options.operands.first().map_or("", String::as_str);
+    let candidates = options
+        .spec
+        .generate("", word, "", &rush.params.aliases.borrow(), &rush.builtins);
+    for candidate in &candidates {
+        writeln!(stdio.stdout, "{}", candidate.text);
+    }
+
+    Ok(if candidates.is_empty() { 1 } else { 0 })
+}
+
+fn parse_options(args: &[String]) -> Result<Options> {
+    let name = &args[0];
+    let mut options = Options::default();
+
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            options.operands.push(arg.clone());
+            break;
+        }
+
+        let mut flags = arg[1..].chars();
+        while let Some(flag) = flags.next() {
+            let action = match flag {
+                'f' => Action::Files,
+                'd' => Action::Directories,
+                'c' => Action::Commands,
+                'v' => Action::Variables,
+                'u' => Action::Users,
+                'b' => Action::Builtins,
+                'a' => Action::Aliases,
+                'A' => match option_value(name, flag, &mut flags, &mut args)?.as_ref() {
+                    "file" => Action::Files,
+                    "directory" => Action::Directories,
+                    "command" => Action::Commands,
+                    "variable" => Action::Variables,
+                    "user" => Action::Users,
+                    "builtin" => Action::Builtins,
+                    "alias" => Action::Aliases,
+                    action => {
+                        return Err(format!("{}: {}: invalid action name", name, action))
+                    }
+                },
+                'W' => Action::Words(option_value(name, flag, &mut flags, &mut args)?),
+                'C' => Action::Command(option_value(name, flag, &mut flags, &mut args)?),
+                'o' => {
+                    match option_value(name, flag, &mut flags, &mut args)?.as_ref() {
+                        "default" => options.spec.default = true,
+                        option => {
+                            return Err(format!("{}: {}: invalid option name", name, option))
+                        }
+                    }
+                    continue;
+                }
+                'p' if name == "complete" => {
+                    options.print = true;
+                    continue;
+                }
+                'r' if name == "complete" => {
+                    options.remove = true;
+                    continue;
+                }
+                _ => {
+                    return Err(format!("{}: -{}: invalid option\n{}", name, flag, usage(name)))
+                }
+            };
+            options.spec.actions.push(action);
+        }
+    }
+    options.operands.extend(args.cloned());
+
+    Ok(options)
+}
+
+fn usage(name: &str) -> String {
+    let operands = if name == "complete" { "[name ...]" } else { "[word]" };
+    format!("{}: usage: {} {} {}", name, name, OPTIONS, operands)
+}
+
+/// The value of the option `flag`, which is the rest of `flags` or the next argument.
+fn option_value(
+    name: &str,
+    flag: char,
+    flags: &mut str::Chars,
+    args: &mut slice::Iter<String>,
+) -> Result<String> {
+    let rest = flags.as_str().to_owned();
+    *flags = "".chars();
+    if !rest.is_empty() {
+        return Ok(rest);
+    }
+
+    args.next().cloned().ok_or_else(|| {
+        format!("{}: -{}: option requires an argument", name, flag)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options> {
+        let args: Vec<_> = args.iter().map(|&arg| String::from(arg)).collect();
+        parse_options(&args)
+    }
+
+    #[test]
+    fn parse_options_test() {
+        assert_eq!(
+            parse(&["complete", "-fd", "-W", "a b", "-Ccmd", "-o", "default", "x", "-y"]).unwrap(),
+            Options {
+                spec: Spec {
+                    actions: vec![
+                        Action::Files,
+                        Action::Directories,
+                        Action::Words(String::from("a b")),
+                        Action::Command(String::from("cmd")),
+                    ],
+                    default: true,
+                },
+                operands: vec![String::from("x"), String::from("-y")],
+                ..Options::default()
+            }
+        );
+        assert_eq!(
+            parse(&["complete", "-pr", "-A", "user", "--", "-x"]).unwrap(),
+            Options {
+                spec: Spec {
+                    actions: vec![Action::Users],
+                    default: false,
+                },
+                print: true,
+                remove: true,
+                operands: vec![String::from("-x")],
+            }
+        );
+
+        assert!(parse(&["complete", "-W"]).is_err());
+        assert!(parse(&["complete", "-A", "job"]).is_err());
+        assert!(parse(&["complete", "-o", "nospace"]).is_err());
+        assert!(parse(&["compgen", "-p"]).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+use std::env;
+use std::path::Path;
+
+use super::cd;
+use super::*;
+
+pub(super) struct Pushd;
+
+impl Builtin for Pushd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "pushd [-n] [dir|+N|-N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Push dir onto the directory stack and change to it, or rotate the stack so that the\n\
+         N-th directory is on the top. -n does not change the current directory."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(pushd(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Popd;
+
+impl Builtin for Popd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "popd [-n] [+N|-N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove the top or the N-th directory from the directory stack, changing to the new\n\
+         top. -n does not change the current directory."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(popd(args, rush, stdio), stdio)
+    }
+}
+
+pub(super) struct Dirs;
+
+impl Builtin for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "dirs [-clpv] [+N|-N]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the directory stack, or its N-th directory. -c clears the stack, -l does not\n\
+         abbreviate $HOME, -p prints one per line and -v numbers them."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(dirs(args, rush, stdio), stdio)
+    }
+}
+
+/// `dirs [-clpv] [+N|-N]`
+///
+/// The directory stack is listed from the top, which is the current directory.
+/// `$HOME` is abbreviated to `~` unless `-l` is given.
+fn dirs(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (mut clear, mut long, mut lines, mut numbered) = (false, false, false, false);
+    let mut index = None;
+    for arg in &args[1..] {
+        if is_index(arg) {
+            index = Some(stack_index("dirs", arg, &stack(rush))?);
+            continue;
+        }
+        if !arg.starts_with('-') || arg.len() < 2 {
+            return Err(format!("dirs: {}: invalid argument\n{}", arg, DIRS_USAGE));
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'c' => clear = true,
+                'l' => long = true,
+                'p' => lines = true,
+                'v' => numbered = true,
+                _ => {
+                    return Err(format!("dirs: -{}: invalid option\n{}", flag, DIRS_USAGE))
+                }
+            }
+        }
+    }
+
+    if clear {
+        rush.params.dir_stack.clear();
+        return Ok(0);
+    }
+
+    let stack = stack(rush);
+    let display = |dir: &str| {
+        if long {
+            dir.to_owned()
+        } else {
+            abbreviate(dir)
+        }
+    };
+    match index {
+        Some(i) if numbered => writeln!(stdio.stdout, "{:2}  {}", i, display(&stack[i])),
+        Some(i) => writeln!(stdio.stdout, "{}", display(&stack[i])),
+        None if numbered => {
+            for (i, dir) in stack.iter().enumerate() {
+                writeln!(stdio.stdout, "{:2}  {}", i, display(dir));
+            }
+        }
+        None if lines => {
+            for dir in &stack {
+                writeln!(stdio.stdout, "{}", display(dir));
+            }
+        }
+        None => {
+            let dirs: Vec<_> = stack.iter().map(|dir| display(dir)).collect();
+            writeln!(stdio.stdout, "{}", dirs.join(" "));
+        }
+    }
+
+    Ok(0)
+}
+
+const DIRS_USAGE: &str = "dirs: usage: dirs [-clpv] [+N] [-N]";
+
+/// `pushd [-n] [dir|+N|-N]`
+///
+/// Without arguments, the top two directories are exchanged.
+/// `+N` and `-N` rotate the stack so that the `N`-th directory, counted from the top or the
+/// bottom, becomes the top.
+/// With `-n`, the current directory is not changed and only the stack is manipulated.
+fn pushd(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (no_change, operand) = options("pushd", args)?;
+    let mut stack = stack(rush);
+
+    match operand {
+        None if stack.len() < 2 => {
+            return Err(String::from("pushd: no other directory"));
+        }
+        None if no_change => return Ok(0),
+        None => stack.swap(0, 1),
+        Some(arg) if is_index(arg) => {
+            let i = stack_index("pushd", arg, &stack)?;
+            if no_change {
+                stack[1..].rotate_left(i.saturating_sub(1));
+            } else {
+                stack.rotate_left(i);
+            }
+        }
+        Some(dir) if no_change => stack.insert(1, dir.clone()),
+        Some(dir) => stack.insert(0, dir.clone()),
+    }
+
+    if !no_change {
+        let dir = change_dir("pushd", &stack[0])?;
+        stack[0] = dir;
+    }
+    rush.params.dir_stack = stack.split_off(1);
+    print_stack(rush, stdio);
+    Ok(0)
+}
+
+/// `popd [-n] [+N|-N]`
+///
+/// Without arguments, the top directory is removed and the current directory is changed to
+/// the new top.
+/// `+N` and `-N` remove the `N`-th directory, counted from the top or the bottom.
+/// With `-n`, the current directory is not changed and only the stack is manipulated.
+fn popd(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let (no_change, operand) = options("popd", args)?;
+    let mut stack = stack(rush);
+    if stack.len() < 2 {
+        return Err(String::from("popd: directory stack empty"));
+    }
+
+    let i = match operand {
+        Some(arg) if is_index(arg) => stack_index("popd", arg, &stack)?,
+        Some(arg) => {
+            return Err(format!("popd: {}: invalid argument\n{}", arg, POPD_USAGE))
+        }
+        None if no_change => 1,
+        None => 0,
+    };
+
+    stack.remove(i);
+    if i == 0 {
+        let dir = change_dir("popd", &stack[0])?;
+        stack[0] = dir;
+    }
+    rush.params.dir_stack = stack.split_off(1);
+    print_stack(rush, stdio);
+    Ok(0)
+}
+
+const POPD_USAGE: &str = "popd: usage: popd [-n] [+N | -N]";
+
+/// Parse `-n` and the optional operand of `pushd` or `popd`.
+fn options<'a>(name: &str, args: &'a [String]) -> Result<(bool, Option<&'a String>)> {
+    let mut no_change = false;
+    let mut operands = args[1..].iter().peekable();
+    while let Some(&arg) = operands.peek() {
+        match arg.as_ref() {
+            "-n" => no_change = true,
+            "--" => {
+                operands.next();
+                break;
+            }
+            _ => break,
+        }
+        operands.next();
+    }
+
+    let operand = operands.next();
+    if operands.next().is_some() {
+        return Err(format!("{}: too many arguments", name));
+    }
+    Ok((no_change, operand))
+}
+
+/// The whole directory stack, with the current directory at the top.
+fn stack(rush: &Rush) -> Vec<String> {
+    let mut stack = vec![cd::logical_dir().display().to_string()];
+    stack.extend(rush.params.dir_stack.iter().cloned());
+    stack
+}
+
+fn is_index(arg: &str) -> bool {
+    (arg.starts_with('+') || arg.starts_with('-'))
+        && arg.len() > 1
+        && arg[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Position in `stack` of `+N`, counted from the top, or `-N`, counted from the bottom.
+fn stack_index(name: &str, arg: &str, stack: &[String]) -> Result<usize> {
+    let error = || {
+        format!(
+            "{}: {}: directory stack index out of range",
+            name, arg
+        )
+    };
+    if !is_index(arg) {
+        return Err(format!("{}: {}: invalid number", name, arg));
+    }
+
+    let n = arg[1..].parse::<usize>().map_err(|_| error())?;
+    let i = if arg.starts_with('-') {
+        stack.len().checked_sub(n + 1).ok_or_else(error)?
+    } else {
+        n
+    };
+    if i < stack.len() {
+        Ok(i)
+    } else {
+        Err(error())
+    }
+}
+
+/// Change the current directory to `dir`, returning the new logical current directory.
+fn change_dir(name: &str, dir: &str) -> Result<String> {
+    cd::set_dir(Path::new(dir), false)
+        .map(|dir| dir.display().to_string())
+        .map_err(|e| format!("{}: {}: {}", name, dir, e))
+}
+
+fn print_stack(rush: &Rush, stdio: &mut Stdio) {
+    let dirs: Vec<_> = stack(rush).iter().map(|dir| abbreviate(dir)).collect();
+    writeln!(stdio.stdout, "{}", dirs.join(" "));
+}
+
+/// `dir` with `$HOME` abbreviated to `~`.
+fn abbreviate(dir: &str) -> String {
+    let home = env::var("HOME").unwrap_or_default();
+    if home.is_empty() || home == "/" {
+        return dir.to_owned();
+    }
+    match dir.strip_prefix(&home) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+        _ => dir.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_index_test() {
+        let stack = vec![String::from("/a"), String::from("/b"), String::from("/c")];
+
+        assert_eq!(stack_index("dirs", "+0", &stack).unwrap(), 0);
+        assert_eq!(stack_index("dirs", "+2", &stack).unwrap(), 2);
+        assert_eq!(stack_index("dirs", "-0", &stack).unwrap(), 2);
+        assert_eq!(stack_index("dirs", "-2", &stack).unwrap(), 0);
+        assert!(stack_index("dirs", "+3", &stack).is_err());
+        assert!(stack_index("dirs", "-3", &stack).is_err());
+        assert!(stack_index("dirs", "+x", &stack).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use super::printf;
+use super::*;
+
+pub(super) struct Echo;
+
+impl Builtin for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "echo [-neE] [arg...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the arguments separated by spaces. -n suppresses the trailing newline, and -e\n\
+         enables backslash escapes."
+    }
+
+    fn takes_help_option(&self) -> bool {
+        false
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(echo(args, stdio), stdio)
+    }
+}
+
+/// `echo [-neE] [arg...]`
+///
+/// `-n` suppresses the trailing newline, and `-e` enables backslash escapes, where `\c`
+/// suppresses any further output.
+fn echo(args: &[String], stdio: &mut Stdio) -> Result<i32> {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut words = args[1..].iter().peekable();
+    while let Some(option) = words.peek() {
+        let flags = match option.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && flags.chars().all(|c| "neE".contains(c)) => flags,
+            _ => break,
+        };
+        for flag in flags.chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        words.next();
+    }
+
+    let mut output = String::new();
+    'words: for (i, word) in words.enumerate() {
+        if i > 0 {
+            output.push(' ');
+        }
+        if !escapes {
+            output.push_str(word);
+            continue;
+        }
+
+        let mut chars = word.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+            } else if !printf::escape(&mut chars, true, &mut output) {
+                newline = false;
+                break 'words;
+            }
+        }
+    }
+    if newline {
+        output.push('\n');
+    }
+
+    stdio.stdout.write_all(output.as_bytes());
+    Ok(0)
+}
@@ -0,0 +1,71 @@
+use super::*;
+
+pub(super) struct Exit;
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "exit [n]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Exit the shell with the exit status n or the one of the last command."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        exit(args, rush, stdio)
+    }
+}
+
+/// `exit [n]`
+///
+/// The shell exits even if `n` is not a number, with the exit status 2.
+fn exit(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> ExitStatus {
+    rush.exit();
+    match args.get(1) {
+        Some(n) => match n.parse::<i32>() {
+            Ok(status) => ExitStatus(status & 0xff),
+            Err(_) => {
+                stdio.error(format_args!("exit: {}: numeric argument required", n));
+                ExitStatus(2)
+            }
+        },
+        None => ExitStatus(rush.params.status),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn exit_test() {
+        let exit = |args: &[&str], status| {
+            let mut rush = Rush::with_command("", String::from("rush"), vec![]);
+            rush.params.status = status;
+            let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+            let (mut out, mut err) = (io::sink(), io::sink());
+            let status = {
+                let mut stdio = Stdio::new(0, &mut out, &mut err, None);
+                super::exit(&args, &mut rush, &mut stdio)
+            };
+            status
+        };
+
+        assert_eq!(exit(&["exit"], 3), ExitStatus(3));
+        assert_eq!(exit(&["exit", "4"], 3), ExitStatus(4));
+        assert_eq!(exit(&["exit", "256"], 3), ExitStatus(0));
+        assert_eq!(exit(&["exit", "-1"], 3), ExitStatus(255));
+        assert_eq!(exit(&["exit", "abc"], 3), ExitStatus(2));
+
+        let script = "false\nexit\nexit 5";
+        assert_eq!(Rush::with_command(script, String::from("rush"), vec![]).repl(), 1);
+        let script = "exit abc\nexit 5";
+        assert_eq!(Rush::with_command(script, String::from("rush"), vec![]).repl(), 2);
+    }
+}
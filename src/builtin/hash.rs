@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use super::*;
+
+const USAGE: &str = "hash: usage: hash [-r] [-p path name] [name ...]";
+
+pub(super) struct Hash;
+
+impl Builtin for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "hash [-r] [-p path name] [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remember the paths of the commands found in $PATH, or print the remembered ones with\n\
+         the number of times they have been run. -r forgets them all, and -p remembers path\n\
+         as the one of name."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(hash(args, rush, stdio), stdio)
+    }
+}
+
+/// `hash [-r] [-p path name] [name ...]`
+///
+/// Without names, the remembered commands are printed with the number of times they have been
+/// run. `-r` forgets all of them, and `-p` remembers `path` as the one of `name`.
+fn hash(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let mut path = None;
+    let mut names = &args[1..];
+    while let Some(option) = names.first() {
+        match option.as_ref() {
+            "--" => {
+                names = &names[1..];
+                break;
+            }
+            "-r" => rush.hash.clear(),
+            "-p" => match names.get(1) {
+                Some(file) => {
+                    path = Some(PathBuf::from(file));
+                    names = &names[1..];
+                }
+                None => return Err(format!("hash: -p: option requires an argument\n{}", USAGE)),
+            },
+            option if option.starts_with('-') && option != "-" => {
+                return Err(format!("hash: {}: invalid option\n{}", option, USAGE))
+            }
+            _ => break,
+        }
+        names = &names[1..];
+    }
+
+    if let Some(path) = path {
+        let name = names.first().ok_or_else(|| String::from(USAGE))?;
+        rush.hash.insert(name, path);
+        return Ok(0);
+    }
+
+    if names.is_empty() {
+        if args[1..].is_empty() {
+            print_table(rush, stdio);
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for name in names {
+        if name.contains('/') || rush.builtins.contains(name) {
+            continue;
+        }
+        if !rush.hash.add(name) {
+            stdio.error(format_args!("hash: {}: not found", name));
+            status = 1;
+        }
+    }
+    Ok(status)
+}
+
+fn print_table(rush: &mut Rush, stdio: &mut Stdio) {
+    let entries = rush.hash.entries();
+    if entries.is_empty() {
+        writeln!(stdio.stdout, "hash: hash table empty");
+        return;
+    }
+
+    writeln!(stdio.stdout, "hits\tcommand");
+    for (_, entry) in entries {
+        writeln!(stdio.stdout, "{:4}\t{}", entry.hits, entry.path.display());
+    }
+}
@@ -0,0 +1,64 @@
+use super::*;
+
+pub(super) struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "history [n], history -c, or history -d offset"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the history, or its last n entries. -c clears the history and -d deletes the\n\
+         entry at offset."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(history(args, rush, stdio), stdio)
+    }
+}
+
+/// `history [n]`, `history -c`, or `history -d offset`
+fn history(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    match args.get(1).map(String::as_str) {
+        Some("-c") => {
+            rush.history.clear();
+            rush.reader.sync_history(&rush.history);
+        }
+        Some("-d") => {
+            let offset = args
+                .get(2)
+                .ok_or_else(|| String::from("history: -d: option requires an argument"))?;
+            let len = rush.history.entries().len() as i64;
+            let index = match offset.parse::<i64>() {
+                Ok(n) if n > 0 => n - 1,
+                Ok(n) if n < 0 => len + n,
+                _ => -1,
+            };
+
+            if index < 0 || !rush.history.delete(index as usize) {
+                return Err(format!("history: {}: history position out of range", offset));
+            }
+            rush.reader.sync_history(&rush.history);
+        }
+        count => {
+            let entries = rush.history.entries();
+            let count = match count {
+                Some(n) => n
+                    .parse::<usize>()
+                    .map_err(|_| format!("history: {}: numeric argument required", n))?,
+                None => entries.len(),
+            };
+
+            let start = entries.len().saturating_sub(count);
+            for (i, entry) in entries.iter().enumerate().skip(start) {
+                writeln!(stdio.stdout, "{:5}  {}", i + 1, entry);
+            }
+        }
+    }
+
+    Ok(0)
+}
@@ -1,10 +1,266 @@
-use errors::{Error, Result};
+//! Builtin commands.
+//!
+//! Each builtin implements `Builtin` and is looked up by name in a `Registry`, which the shell
+//! shares with the line editor to complete and highlight builtin names.
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+
+use rush::Rush;
+
+pub(super) mod alias;
 pub(super) mod cd;
+pub(super) mod command;
+pub(super) mod complete;
+pub(super) mod dirs;
+pub(super) mod echo;
+pub(super) mod exit;
+pub(super) mod hash;
+pub(super) mod history;
+pub(super) mod printf;
+pub(super) mod read;
+pub(super) mod source;
+pub(super) mod test;
+
+/// Result of the function running a builtin, whose error is a message reported with the exit
+/// status 1.
+type Result<T> = ::std::result::Result<T, String>;
+
+/// A command run in the shell process.
+pub(super) trait Builtin {
+    fn name(&self) -> &'static str;
+
+    /// Usage of the command, such as `cd [-L|-P] [dir]`.
+    fn synopsis(&self) -> &'static str;
+
+    /// Description of the command, shown by `help`.
+    fn help(&self) -> &'static str;
+
+    /// Whether `--help` as the only argument prints the help instead of running the command.
+    /// Commands taking it as an operand, such as `echo`, return false.
+    fn takes_help_option(&self) -> bool {
+        true
+    }
+
+    /// Run the command with `args`, including the name, in the shell `rush`.
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus;
+}
+
+/// Exit status of a builtin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct ExitStatus(pub(super) i32);
+
+impl ExitStatus {
+    pub(super) const SUCCESS: ExitStatus = ExitStatus(0);
+    pub(super) const FAILURE: ExitStatus = ExitStatus(1);
+
+    /// The exit status of `result`, reporting the error to `stdio` if failed.
+    fn of(result: Result<i32>, stdio: &mut Stdio) -> Self {
+        match result {
+            Ok(status) => ExitStatus(status),
+            Err(message) => {
+                stdio.error(message);
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+/// Standard input, output and error of a builtin.
+///
+/// The file descriptors 0 and 1 of the shell are also redirected while a builtin runs, so that
+/// the commands it runs in turn, such as the ones of a sourced file, inherit the redirections.
+pub(super) struct Stdio<'a> {
+    pub(super) stdin: RawFd,
+    pub(super) stdout: Output<'a>,
+    pub(super) stderr: Output<'a>,
+    /// Location in the script being run, shown in error messages.
+    location: Option<String>,
+}
+
+impl<'a> Stdio<'a> {
+    pub(super) fn new(
+        stdin: RawFd,
+        stdout: &'a mut dyn Write,
+        stderr: &'a mut dyn Write,
+        location: Option<String>,
+    ) -> Self {
+        Self {
+            stdin,
+            stdout: Output::new(stdout),
+            stderr: Output::new(stderr),
+            location,
+        }
+    }
+
+    /// Print `message` to the standard error as an error of the shell.
+    pub(super) fn error<D: fmt::Display>(&mut self, message: D) {
+        match self.location {
+            Some(ref location) => writeln!(self.stderr, "rush: {}: {}", location, message),
+            None => writeln!(self.stderr, "rush: {}", message),
+        }
+    }
+}
+
+/// An output stream of a builtin, which keeps the first write error instead of returning it,
+/// so that the builtin prints without checking each write.
+pub(super) struct Output<'a> {
+    inner: &'a mut dyn Write,
+    error: Option<io::Error>,
+}
+
+impl<'a> Output<'a> {
+    fn new(inner: &'a mut dyn Write) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Write formatted output, used by `write!` and `writeln!`.
+    pub(super) fn write_fmt(&mut self, args: fmt::Arguments) {
+        if self.error.is_none() {
+            self.error = self.inner.write_fmt(args).err();
+        }
+    }
+
+    pub(super) fn write_all(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            self.error = self.inner.write_all(bytes).err();
+        }
+    }
+
+    /// Flush the stream, returning the first error of the writes so far.
+    fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.inner.flush(),
+        }
+    }
+}
+
+/// Run `builtin` with `args`, or print its help if it takes `--help` and it is the only
+/// argument. A write error to the standard output fails with the exit status 1.
+pub(super) fn run(
+    builtin: &dyn Builtin,
+    rush: &mut Rush,
+    args: &[String],
+    stdio: &mut Stdio,
+) -> ExitStatus {
+    let status = if builtin.takes_help_option() && args.len() == 2 && args[1] == "--help" {
+        print_help(builtin, stdio);
+        ExitStatus::SUCCESS
+    } else {
+        builtin.run(rush, args, stdio)
+    };
+
+    match stdio.stdout.flush() {
+        Ok(()) => status,
+        Err(e) => {
+            stdio.error(format_args!("{}: write error: {}", builtin.name(), e));
+            ExitStatus::FAILURE
+        }
+    }
+}
+
+/// Set `$PWD` to the current directory when the shell starts, as `cd` keeps it.
+pub(super) fn init_pwd() {
+    cd::init_pwd();
+}
+
+/// The logical current directory kept by `cd` in `$PWD`, or the physical one if not set.
+pub(super) fn logical_dir() -> PathBuf {
+    cd::logical_dir()
+}
+
+/// Evaluate the words of `[[ ... ]]`.
+pub(super) fn conditional(rush: &mut Rush, words: &[String], stdio: &mut Stdio) -> ExitStatus {
+    test::conditional(rush, words, stdio)
+}
+
+/// Print the synopsis and the description of `builtin`.
+fn print_help(builtin: &dyn Builtin, stdio: &mut Stdio) {
+    writeln!(stdio.stdout, "{}: {}", builtin.name(), builtin.synopsis());
+    for line in builtin.help().lines() {
+        writeln!(stdio.stdout, "    {}", line);
+    }
+}
+
+/// Builtins by name.
+pub(super) struct Registry {
+    builtins: BTreeMap<&'static str, Box<dyn Builtin>>,
+}
+
+impl Registry {
+    /// Register `builtin`, replacing the one with the same name if any.
+    pub(super) fn register(&mut self, builtin: Box<dyn Builtin>) {
+        self.builtins.insert(builtin.name(), builtin);
+    }
+
+    pub(super) fn get(&self, name: &str) -> Option<&dyn Builtin> {
+        self.builtins.get(name).map(|builtin| &**builtin)
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    /// Names of the builtins, sorted.
+    pub(super) fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.builtins.keys().cloned()
+    }
+}
+
+impl Default for Registry {
+    /// Registry of all the builtins of the shell.
+    fn default() -> Self {
+        let mut registry = Registry {
+            builtins: BTreeMap::new(),
+        };
+        let builtins: Vec<Box<dyn Builtin>> = vec![
+            Box::new(cd::Cd),
+            Box::new(cd::Pwd),
+            Box::new(dirs::Pushd),
+            Box::new(dirs::Popd),
+            Box::new(dirs::Dirs),
+            Box::new(echo::Echo),
+            Box::new(printf::Printf),
+            Box::new(read::Read),
+            Box::new(test::Test),
+            Box::new(test::Bracket),
+            Box::new(source::Source),
+            Box::new(source::Dot),
+            Box::new(source::Return),
+            Box::new(exit::Exit),
+            Box::new(history::History),
+            Box::new(complete::Complete),
+            Box::new(complete::Compgen),
+            Box::new(alias::Alias),
+            Box::new(alias::Unalias),
+            Box::new(command::Help),
+            Box::new(command::Type),
+            Box::new(command::Command),
+            Box::new(hash::Hash),
+            Box::new(command::BuiltinCommand),
+            Box::new(command::Which),
+        ];
+        for builtin in builtins {
+            registry.register(builtin);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub(super) fn exec(argument_list: &[String]) -> Option<Result<()>> {
-    match argument_list[0].as_ref() {
-        "cd" => Some(cd::cd(argument_list)),
-        _ => None,
+    #[test]
+    fn registry_test() {
+        let registry = Registry::default();
+        assert!(registry.contains("cd"));
+        assert!(!registry.contains("ls"));
+        assert_eq!(registry.get("[").map(|builtin| builtin.synopsis()), Some("[ expression ]"));
+        assert_eq!(registry.names().take(3).collect::<Vec<_>>(), vec![".", "[", "alias"]);
     }
 }
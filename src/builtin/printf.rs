@@ -0,0 +1,628 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use std::{char, env, ffi, ptr};
+
+use libc;
+
+use super::*;
+
+const USAGE: &str = "printf: usage: printf [-v var] format [arguments]";
+
+pub(super) struct Printf;
+
+impl Builtin for Printf {
+    fn name(&self) -> &'static str {
+        "printf"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "printf [-v var] format [arguments]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the arguments formatted by format, reused until they are consumed. -v assigns\n\
+         the output to var instead."
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(printf(args, stdio), stdio)
+    }
+}
+
+/// `printf [-v var] format [arguments]`
+///
+/// The format is reused as long as arguments remain, and missing arguments are taken as empty
+/// strings or zero.
+/// Besides the conversions of printf(3), `%b` expands backslash escapes in the argument,
+/// `%q` quotes it for the shell, and `%(fmt)T` formats it as seconds since the epoch by
+/// strftime(3), where -1 or no argument is the current time.
+/// With `-v`, the output is assigned to the variable `var` instead of being printed.
+fn printf(args: &[String], stdio: &mut Stdio) -> Result<i32> {
+    let mut args = &args[1..];
+    let mut var = None;
+    loop {
+        match args.first().map(String::as_str) {
+            Some("-v") => {
+                let name = args.get(1).ok_or_else(|| {
+                    format!(
+                        "printf: -v: option requires an argument\n{}",
+                        USAGE
+                    )
+                })?;
+                if !is_name(name) {
+                    return Err(format!("printf: `{}': not a valid identifier", name));
+                }
+                var = Some(name);
+                args = &args[2..];
+            }
+            Some("--") => {
+                args = &args[1..];
+                break;
+            }
+            _ => break,
+        }
+    }
+
+    let (format, args) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(String::from(USAGE)),
+    };
+
+    let mut formatter = Formatter {
+        args,
+        output: String::new(),
+        invalid: vec![],
+    };
+    loop {
+        let remaining = formatter.args.len();
+        if !formatter.format(format)? || formatter.args.is_empty() {
+            break;
+        }
+        if formatter.args.len() == remaining {
+            break;
+        }
+    }
+
+    match var {
+        Some(var) => env::set_var(var, &formatter.output),
+        None => stdio.stdout.write_all(formatter.output.as_bytes()),
+    }
+
+    for arg in &formatter.invalid {
+        stdio.error(format_args!("printf: {}: invalid number", arg));
+    }
+    Ok(if formatter.invalid.is_empty() { 0 } else { 1 })
+}
+
+struct Formatter<'a> {
+    /// Arguments not consumed yet.
+    args: &'a [String],
+    output: String,
+    /// Arguments which are not valid numbers, making the exit status 1.
+    invalid: Vec<&'a str>,
+}
+
+/// A conversion specification such as `%-8.3s`.
+#[derive(Debug, Default, PartialEq)]
+struct Spec {
+    /// Flags out of `-+ #0`.
+    flags: String,
+    width: Option<i64>,
+    precision: Option<i64>,
+    conversion: char,
+}
+
+impl<'a> Formatter<'a> {
+    /// Format `format` once, consuming arguments.
+    /// Returns false if the output is stopped by `\c` in an argument of `%b`.
+    fn format(&mut self, format: &str) -> Result<bool> {
+        let mut chars = format.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    escape(&mut chars, false, &mut self.output);
+                }
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    self.output.push('%');
+                }
+                '%' => {
+                    if !self.conversion(&mut chars)? {
+                        return Ok(false);
+                    }
+                }
+                c => self.output.push(c),
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Format a conversion specification following `%` in `chars`.
+    fn conversion(&mut self, chars: &mut Peekable<Chars>) -> Result<bool> {
+        let mut spec = Spec::default();
+        while let Some(&c) = chars.peek() {
+            if !"-+ #0".contains(c) {
+                break;
+            }
+            spec.flags.push(c);
+            chars.next();
+        }
+
+        spec.width = self.number_or_argument(chars, "field width")?;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            spec.precision = Some(self.number_or_argument(chars, "precision")?.unwrap_or(0));
+        }
+        if let Some(width) = spec.width {
+            if width < 0 {
+                spec.flags.push('-');
+                spec.width = Some(-width);
+            }
+        }
+        while chars.peek().is_some_and(|&c| "hlLjzt".contains(c)) {
+            chars.next();
+        }
+
+        let conversion = chars
+            .next()
+            .ok_or_else(|| String::from("printf: `%': missing format character"))?;
+        spec.conversion = conversion;
+
+        match conversion {
+            'd' | 'i' | 'o' | 'u' | 'x' | 'X' => {
+                let value = self.integer();
+                let c_spec = spec.c_format("ll");
+                self.output.push_str(&c_format(&c_spec, Value::Int(value)));
+            }
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'a' | 'A' => {
+                let value = self.float();
+                let c_spec = spec.c_format("");
+                self.output
+                    .push_str(&c_format(&c_spec, Value::Float(value)));
+            }
+            'c' => {
+                let c = self.string().chars().next().map(String::from);
+                spec.precision = None;
+                self.push_padded(&spec, &c.unwrap_or_default());
+            }
+            's' => {
+                let s = self.string().to_owned();
+                self.push_padded(&spec, &s);
+            }
+            'b' => {
+                let mut s = String::new();
+                let mut chars = self.string().chars().peekable();
+                let mut stopped = false;
+                while let Some(c) = chars.next() {
+                    if c != '\\' {
+                        s.push(c);
+                    } else if !escape(&mut chars, true, &mut s) {
+                        stopped = true;
+                        break;
+                    }
+                }
+                self.push_padded(&spec, &s);
+                if stopped {
+                    return Ok(false);
+                }
+            }
+            'q' => {
+                let s = quote(self.string());
+                self.push_padded(&spec, &s);
+            }
+            '(' => {
+                let mut time_format = String::new();
+                loop {
+                    match chars.next() {
+                        Some(')') if chars.peek() == Some(&'T') => break,
+                        Some(c) => time_format.push(c),
+                        None => {
+                            return Err(String::from(
+                                "printf: `(': invalid time format specification",
+                            ))
+                        }
+                    }
+                }
+                chars.next();
+
+                let time = match self.args.first() {
+                    Some(_) => self.integer(),
+                    None => -1,
+                };
+                let s = strftime(&time_format, time);
+                self.push_padded(&spec, &s);
+            }
+            c => {
+                return Err(format!("printf: `{}': invalid format character", c))
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// A width or precision given by digits or `*`, which takes an argument.
+    /// It is an error if the number is out of the range of `int`, called `name` in the message.
+    fn number_or_argument(
+        &mut self,
+        chars: &mut Peekable<Chars>,
+        name: &str,
+    ) -> Result<Option<i64>> {
+        let (text, number) = if chars.peek() == Some(&'*') {
+            chars.next();
+            let args = self.args;
+            let text = args.first().map_or("", String::as_str);
+            (text.to_owned(), self.integer())
+        } else {
+            let mut digits = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_digit() {
+                    break;
+                }
+                digits.push(c);
+                chars.next();
+            }
+            if digits.is_empty() {
+                return Ok(None);
+            }
+            let number = digits.parse().unwrap_or(i64::MAX);
+            (digits, number)
+        };
+
+        if number.abs() > i64::from(libc::c_int::MAX) {
+            return Err(format!("printf: {}: invalid {}", text, name));
+        }
+        Ok(Some(number))
+    }
+
+    fn push_padded(&mut self, spec: &Spec, s: &str) {
+        let s: String = match spec.precision {
+            Some(precision) => s.chars().take(precision.max(0) as usize).collect(),
+            None => s.to_owned(),
+        };
+        let padding = spec.width.unwrap_or(0).max(0) as usize;
+        let padding = " ".repeat(padding.saturating_sub(s.chars().count()));
+
+        if spec.flags.contains('-') {
+            self.output.push_str(&s);
+            self.output.push_str(&padding);
+        } else {
+            self.output.push_str(&padding);
+            self.output.push_str(&s);
+        }
+    }
+
+    /// The next argument, or an empty string if none is left.
+    fn string(&mut self) -> &'a str {
+        match self.args.split_first() {
+            Some((arg, rest)) => {
+                self.args = rest;
+                arg
+            }
+            None => "",
+        }
+    }
+
+    /// The next argument as an integer, which may be octal by a leading `0`, hexadecimal by
+    /// `0x`, or the character code after a quote.
+    fn integer(&mut self) -> i64 {
+        let arg = self.string();
+        match parse_integer(arg) {
+            Some(value) => value,
+            None => {
+                self.invalid_number(arg);
+                0
+            }
+        }
+    }
+
+    fn float(&mut self) -> f64 {
+        let arg = self.string();
+        let value = match character_code(arg) {
+            Some(code) => Some(code as f64),
+            None if arg.trim().is_empty() => Some(0.0),
+            None => arg.trim().parse().ok(),
+        };
+        match value {
+            Some(value) => value,
+            None => {
+                self.invalid_number(arg);
+                0.0
+            }
+        }
+    }
+
+    fn invalid_number(&mut self, arg: &'a str) {
+        self.invalid.push(arg);
+    }
+}
+
+impl Spec {
+    /// The specification for printf(3), with the length modifier `length`.
+    fn c_format(&self, length: &str) -> String {
+        let mut spec = format!("%{}", self.flags);
+        if let Some(width) = self.width {
+            spec.push_str(&width.to_string());
+        }
+        if let Some(precision) = self.precision {
+            spec.push_str(&format!(".{}", precision));
+        }
+        spec.push_str(length);
+        spec.push(self.conversion);
+        spec
+    }
+}
+
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+/// Format `value` by snprintf(3) with the specification `spec`.
+fn c_format(spec: &str, value: Value) -> String {
+    let spec = match ffi::CString::new(spec) {
+        Ok(spec) => spec,
+        Err(_) => return String::new(),
+    };
+    let print = |buf: *mut libc::c_char, len: usize| unsafe {
+        match value {
+            Value::Int(value) => libc::snprintf(buf, len, spec.as_ptr(), value),
+            Value::Float(value) => libc::snprintf(buf, len, spec.as_ptr(), value),
+        }
+    };
+
+    let len = print(ptr::null_mut(), 0);
+    if len < 0 {
+        return String::new();
+    }
+    let mut buf = vec![0u8; len as usize + 1];
+    print(buf.as_mut_ptr() as *mut libc::c_char, buf.len());
+    buf.truncate(len as usize);
+    String::from_utf8_lossy(&buf).into_owned()
+}
+
+fn parse_integer(arg: &str) -> Option<i64> {
+    if let Some(code) = character_code(arg) {
+        return Some(code);
+    }
+
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Some(0);
+    }
+    let (negative, digits) = match arg.as_bytes()[0] {
+        b'-' => (true, &arg[1..]),
+        b'+' => (false, &arg[1..]),
+        _ => (false, arg),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse::<u64>().ok()?
+    };
+
+    let value = value as i64;
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// The code of the character following a leading `'` or `"`.
+fn character_code(arg: &str) -> Option<i64> {
+    if arg.starts_with('\'') || arg.starts_with('"') {
+        Some(arg[1..].chars().next().map_or(0, |c| c as i64))
+    } else {
+        None
+    }
+}
+
+/// Decode the escape sequence following `\` in `chars` onto `output`.
+///
+/// For `echo -e` and `%b`, an octal code is `\0nnn` rather than `\nnn`, and `\c` stops the
+/// output, which is notified by returning false.
+pub(super) fn escape(chars: &mut Peekable<Chars>, echo: bool, output: &mut String) -> bool {
+    let c = match chars.next() {
+        Some(c) => c,
+        None => {
+            output.push('\\');
+            return true;
+        }
+    };
+
+    match c {
+        'a' => output.push('\x07'),
+        'b' => output.push('\x08'),
+        'e' | 'E' => output.push('\x1b'),
+        'f' => output.push('\x0c'),
+        'n' => output.push('\n'),
+        'r' => output.push('\r'),
+        't' => output.push('\t'),
+        'v' => output.push('\x0b'),
+        '\\' => output.push('\\'),
+        '"' if !echo => output.push('"'),
+        'c' if echo => return false,
+        '0'..='7' if !echo || c == '0' => {
+            let (first, digits) = if echo {
+                (0, 3)
+            } else {
+                (c.to_digit(8).unwrap(), 2)
+            };
+            let code = digits_value(chars, first, 8, digits);
+            output.extend(char::from_u32(code));
+        }
+        'x' | 'u' | 'U' => {
+            let digits = match c {
+                'x' => 2,
+                'u' => 4,
+                _ => 8,
+            };
+            if chars.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                let code = digits_value(chars, 0, 16, digits);
+                output.extend(char::from_u32(code));
+            } else {
+                output.push('\\');
+                output.push(c);
+            }
+        }
+        c => {
+            output.push('\\');
+            output.push(c);
+        }
+    }
+
+    true
+}
+
+/// Read up to `max` digits in `radix` from `chars`, following the value `first`.
+fn digits_value(chars: &mut Peekable<Chars>, first: u32, radix: u32, max: usize) -> u32 {
+    let mut value = first;
+    for _ in 0..max {
+        match chars.peek().and_then(|c| c.to_digit(radix)) {
+            Some(digit) => {
+                value = value.wrapping_mul(radix).wrapping_add(digit);
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    value
+}
+
+/// Quote `s` with backslashes so that the shell reads it as one word, or with `$'...'` if it
+/// contains control characters.
+fn quote(s: &str) -> String {
+    if s.is_empty() {
+        return String::from("''");
+    }
+
+    let mut quoted = String::new();
+    if s.chars().any(char::is_control) {
+        quoted.push_str("$'");
+        for c in s.chars() {
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\\' | '\'' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+    } else {
+        for c in s.chars() {
+            if " \t!\"#$&'()*,;<=>?[\\]^`{|}~".contains(c) {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+    }
+    quoted
+}
+
+/// Format `time`, in seconds since the epoch or -1 for now, by strftime(3).
+fn strftime(format: &str, time: i64) -> String {
+    let format = match ffi::CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    if format.as_bytes().is_empty() {
+        return String::new();
+    }
+
+    unsafe {
+        let time = if time == -1 {
+            libc::time(ptr::null_mut())
+        } else {
+            time as libc::time_t
+        };
+        let mut tm = ::std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+
+        let mut buf = vec![0u8; 1024];
+        let len = libc::strftime(
+            buf.as_mut_ptr() as *mut libc::c_char,
+            buf.len(),
+            format.as_ptr(),
+            &tm,
+        );
+        buf.truncate(len);
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+        && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: &str, args: &[&str]) -> (String, i32) {
+        let args: Vec<_> = args.iter().map(|&arg| String::from(arg)).collect();
+        let mut formatter = Formatter {
+            args: &args,
+            output: String::new(),
+            invalid: vec![],
+        };
+        while formatter.format(format).unwrap() && !formatter.args.is_empty() {}
+        (formatter.output, formatter.invalid.len() as i32)
+    }
+
+    #[test]
+    fn format_test() {
+        assert_eq!(format("%s-%d\\n", &["a", "1", "b"]).0, "a-1\nb-0\n");
+        assert_eq!(
+            format("[%5s|%-3s|%.2s]", &["ab", "c", "xyz"]).0,
+            "[   ab|c  |xy]"
+        );
+        assert_eq!(
+            format("%05.1f %x %o %c %%", &["3.14159", "255", "8", "xy"]).0,
+            "003.1 ff 10 x %"
+        );
+        assert_eq!(format("%d %d %d", &["0x10", "010", "'A"]).0, "16 8 65");
+        assert_eq!(format("%*d|%-*d|", &["3", "1", "2", "2"]).0, "  1|2 |");
+        assert_eq!(format("%d", &["x"]), (String::from("0"), 1));
+        assert_eq!(format("%b|", &["a\\tb\\0101", "c\\cd", "e"]).0, "a\tbA|c");
+        assert_eq!(format("\\101\\x42\\u00e9", &[]).0, "ABé");
+        assert_eq!(
+            format("%q %q %q", &["a b", "", "x\ny"]).0,
+            "a\\ b '' $'x\\ny'"
+        );
+        assert_eq!(format("%(%Y)T", &["0"]).0.len(), 4);
+    }
+
+    #[test]
+    fn out_of_range_test() {
+        let args = vec![String::from("-99999999999")];
+        let mut formatter = Formatter {
+            args: &args,
+            output: String::new(),
+            invalid: vec![],
+        };
+        assert!(formatter.format("%99999999999d").is_err());
+        assert!(formatter.format("%.*d").is_err());
+        assert!(formatter.format("%.2147483647s").is_ok());
+    }
+
+    #[test]
+    fn quote_test() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("$HOME's"), "\\$HOME\\'s");
+        assert_eq!(quote("tab\there"), "$'tab\\there'");
+    }
+}
@@ -0,0 +1,480 @@
+use std::{char, env, io, mem};
+use std::os::unix::io::RawFd;
+
+use libc;
+
+use super::*;
+
+const USAGE: &str =
+    "read: usage: read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] \
+     [name ...]";
+
+pub(super) struct Read;
+
+impl Builtin for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [name ...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Read a line from the standard input and split it by $IFS into the names, or into\n\
+         $REPLY if none is given."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(read(args, rush, stdio), stdio)
+    }
+}
+
+/// Exit status when `-t` times out.
+const TIMEOUT_STATUS: i32 = 142;
+
+/// Options of `read`.
+#[derive(Debug, PartialEq)]
+struct Options {
+    /// `-r`
+    raw: bool,
+    /// `-s`
+    silent: bool,
+    /// `-a`
+    array: Option<String>,
+    /// `-d`
+    delimiter: u8,
+    /// `-n`
+    count: Option<usize>,
+    /// `-p`
+    prompt: Option<String>,
+    /// `-t`, in seconds.
+    timeout: Option<f64>,
+    names: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            raw: false,
+            silent: false,
+            array: None,
+            delimiter: b'\n',
+            count: None,
+            prompt: None,
+            timeout: None,
+            names: vec![],
+        }
+    }
+}
+
+/// `read [-rs] [-a array] [-d delim] [-n nchars] [-p prompt] [-t timeout] [name ...]`
+///
+/// A line read from the standard input is split into fields by `$IFS`, which are assigned to
+/// the names in order, the last name taking the rest of the line. Without names, the line is
+/// assigned to `REPLY` as it is.
+/// Unless `-r` is given, `\` escapes the next character and removes a following newline.
+/// Returns 1 at the end of file, and 142 if timed out.
+fn read(args: &[String], rush: &mut Rush, stdio: &mut Stdio) -> Result<i32> {
+    let options = parse_options(args)?;
+    for name in options.array.iter().chain(&options.names) {
+        if !is_name(name) {
+            return Err(format!("read: `{}': not a valid identifier", name));
+        }
+    }
+
+    let terminal = unsafe { libc::isatty(stdio.stdin) } == 1;
+    if let Some(ref prompt) = options.prompt {
+        if terminal {
+            write!(stdio.stderr, "{}", prompt);
+        }
+    }
+
+    if options.timeout == Some(0.0) {
+        return Ok(if wait_input(stdio.stdin, Some(0.0))? { 0 } else { 1 });
+    }
+
+    let termios = if terminal {
+        set_terminal(stdio.stdin, &options)
+    } else {
+        None
+    };
+    let result = read_input(stdio.stdin, &options);
+    if let Some(termios) = termios {
+        unsafe { libc::tcsetattr(stdio.stdin, libc::TCSANOW, &termios) };
+        if options.silent {
+            writeln!(stdio.stderr);
+        }
+    }
+    let (input, status) = result?;
+
+    let ifs = env::var("IFS").unwrap_or_else(|_| String::from(" \t\n"));
+    if let Some(ref array) = options.array {
+        let fields = split(&input, &ifs, usize::MAX);
+        env::remove_var(array);
+        rush.params.arrays.insert(array.clone(), fields);
+    } else if options.names.is_empty() {
+        let line: String = input.into_iter().map(|(c, _)| c).collect();
+        assign(rush, "REPLY", &line);
+    } else {
+        let fields = split(&input, &ifs, options.names.len());
+        for (i, name) in options.names.iter().enumerate() {
+            assign(rush, name, fields.get(i).map_or("", String::as_str));
+        }
+    }
+
+    Ok(status)
+}
+
+fn parse_options(args: &[String]) -> Result<Options> {
+    let mut options = Options::default();
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            options.names.push(arg.clone());
+            break;
+        }
+
+        let mut flags = arg[1..].chars();
+        while let Some(flag) = flags.next() {
+            match flag {
+                'r' => options.raw = true,
+                's' => options.silent = true,
+                'a' | 'd' | 'n' | 'p' | 't' => {
+                    let rest = flags.as_str();
+                    let value = if rest.is_empty() {
+                        args.next().cloned().ok_or_else(|| {
+                            format!(
+                                "read: -{}: option requires an argument\n{}",
+                                flag, USAGE
+                            )
+                        })?
+                    } else {
+                        rest.to_owned()
+                    };
+                    flags = "".chars();
+
+                    match flag {
+                        'a' => options.array = Some(value),
+                        'd' => options.delimiter = value.bytes().next().unwrap_or(0),
+                        'n' => {
+                            options.count = Some(value.parse().map_err(|_| {
+                                format!("read: {}: invalid number", value)
+                            })?)
+                        }
+                        'p' => options.prompt = Some(value),
+                        _ => {
+                            options.timeout = Some(
+                                value
+                                    .parse()
+                                    .ok()
+                                    .filter(|&timeout: &f64| timeout >= 0.0)
+                                    .ok_or_else(|| {
+                                        format!(
+                                            "read: {}: invalid timeout specification",
+                                            value
+                                        )
+                                    })?,
+                            )
+                        }
+                    }
+                }
+                _ => {
+                    return Err(format!("read: -{}: invalid option\n{}", flag, USAGE))
+                }
+            }
+        }
+    }
+    options.names.extend(args.cloned());
+
+    Ok(options)
+}
+
+/// Disable echo for `-s`, and line buffering for `-n` or `-d`, on the terminal `fd`, returning
+/// the previous settings.
+fn set_terminal(fd: RawFd, options: &Options) -> Option<libc::termios> {
+    if !options.silent && options.count.is_none() && options.delimiter == b'\n' {
+        return None;
+    }
+
+    unsafe {
+        let mut termios = mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return None;
+        }
+        let saved = termios;
+        if options.silent {
+            termios.c_lflag &= !libc::ECHO;
+        }
+        if options.count.is_some() || options.delimiter != b'\n' {
+            termios.c_lflag &= !libc::ICANON;
+            termios.c_cc[libc::VMIN] = 1;
+            termios.c_cc[libc::VTIME] = 0;
+        }
+        libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        Some(saved)
+    }
+}
+
+/// Read `fd` up to the delimiter, returning the characters with whether each is escaped by `\`,
+/// and the exit status.
+///
+/// The input is read byte by byte, so that the rest is left for the next command.
+fn read_input(fd: RawFd, options: &Options) -> Result<(Vec<(char, bool)>, i32)> {
+    let deadline = options
+        .timeout
+        .map(|timeout| (timeout, ::std::time::Instant::now()));
+    let mut bytes = vec![];
+    let mut escaped = vec![];
+    let mut escape = false;
+    let mut count = 0;
+    // Continuation bytes left of the last character, which complete it even after `-n` chars.
+    let mut continuation = 0;
+    let mut status = 0;
+
+    loop {
+        if options.count.is_some_and(|n| count >= n) && continuation == 0 {
+            break;
+        }
+        if let Some((timeout, start)) = deadline {
+            let remaining = timeout - start.elapsed().as_secs_f64();
+            if remaining <= 0.0 || !wait_input(fd, Some(remaining))? {
+                status = TIMEOUT_STATUS;
+                break;
+            }
+        }
+
+        let mut byte = 0u8;
+        let len = unsafe { libc::read(fd, &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(format!("read: read error: {}", e));
+        }
+        if len == 0 {
+            status = 1;
+            break;
+        }
+
+        let previous_escape = mem::replace(&mut escape, false);
+        if previous_escape {
+            if byte == b'\n' {
+                continue;
+            }
+        } else if byte == options.delimiter {
+            break;
+        } else if byte == b'\\' && !options.raw {
+            escape = true;
+            continue;
+        }
+
+        // Count characters, not continuation bytes of UTF-8.
+        if byte & 0xc0 != 0x80 {
+            count += 1;
+            continuation = utf8_len(byte) - 1;
+        } else {
+            continuation = continuation.saturating_sub(1);
+        }
+        escaped.push(!options.raw && previous_escape);
+        bytes.push(byte);
+    }
+
+    // Invalid UTF-8 is replaced byte by byte, so that `escaped` is indexed by the first byte.
+    let mut chars = vec![];
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = utf8_len(bytes[offset]).min(bytes.len() - offset);
+        let c = match ::std::str::from_utf8(&bytes[offset..offset + len]) {
+            Ok(s) => s.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER),
+            Err(_) => char::REPLACEMENT_CHARACTER,
+        };
+        let len = if c == char::REPLACEMENT_CHARACTER && len > 1 {
+            1
+        } else {
+            len
+        };
+        chars.push((c, escaped[offset]));
+        offset += len;
+    }
+    Ok((chars, status))
+}
+
+/// Length of the UTF-8 sequence starting with `byte`.
+fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+/// Wait until `fd` is readable or `timeout` seconds pass.
+fn wait_input(fd: RawFd, timeout: Option<f64>) -> Result<bool> {
+    let mut fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.map_or(-1, |timeout| (timeout * 1000.0).ceil() as libc::c_int);
+    match unsafe { libc::poll(&mut fd, 1, timeout) } {
+        n if n < 0 => Err(format!("read: {}", io::Error::last_os_error())),
+        n => Ok(n > 0),
+    }
+}
+
+/// Split `input` into at most `max` fields by `ifs`, where escaped characters are not
+/// separators.
+///
+/// Whitespace in `ifs` is trimmed around the input and separates fields as a sequence, while
+/// each of the other characters separates fields by itself with adjacent whitespace.
+/// The last field is the rest of the input.
+fn split(input: &[(char, bool)], ifs: &str, max: usize) -> Vec<String> {
+    let is_separator = |&(c, escaped): &(char, bool)| !escaped && ifs.contains(c);
+    let is_whitespace =
+        |&(c, escaped): &(char, bool)| !escaped && ifs.contains(c) && " \t\n".contains(c);
+
+    let mut fields = vec![];
+    if ifs.is_empty() {
+        fields.push(input.iter().map(|&(c, _)| c).collect());
+        return fields;
+    }
+
+    let mut rest = input;
+    while rest.first().is_some_and(is_whitespace) {
+        rest = &rest[1..];
+    }
+    while rest.last().is_some_and(is_whitespace) {
+        rest = &rest[..rest.len() - 1];
+    }
+
+    while !rest.is_empty() {
+        if fields.len() + 1 == max {
+            fields.push(rest.iter().map(|&(c, _)| c).collect());
+            break;
+        }
+
+        let end = rest.iter().position(is_separator).unwrap_or(rest.len());
+        fields.push(rest[..end].iter().map(|&(c, _)| c).collect());
+        rest = &rest[end..];
+
+        while rest.first().is_some_and(is_whitespace) {
+            rest = &rest[1..];
+        }
+        if rest
+            .first()
+            .is_some_and(|c| is_separator(c) && !is_whitespace(c))
+        {
+            rest = &rest[1..];
+            while rest.first().is_some_and(is_whitespace) {
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    fields
+}
+
+/// Assign `value` to the variable `name`, replacing an array of the name.
+fn assign(rush: &mut Rush, name: &str, value: &str) {
+    rush.params.arrays.remove(name);
+    env::set_var(name, value);
+}
+
+fn is_name(name: &str) -> bool {
+    name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+        && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<(char, bool)> {
+        s.chars().map(|c| (c, false)).collect()
+    }
+
+    #[test]
+    fn split_test() {
+        assert_eq!(split(&chars("  a  b c  "), " \t\n", 2), vec!["a", "b c"]);
+        assert_eq!(split(&chars("a b"), " \t\n", 3), vec!["a", "b"]);
+        assert_eq!(
+            split(&chars("a:b::c"), ":", usize::MAX),
+            vec!["a", "b", "", "c"]
+        );
+        assert_eq!(split(&chars("a : b"), " :", usize::MAX), vec!["a", "b"]);
+        assert_eq!(split(&chars(" a b "), "", 2), vec![" a b "]);
+        assert_eq!(split(&[], " ", 2), Vec::<String>::new());
+
+        let mut input = chars("a b");
+        input[1].1 = true;
+        assert_eq!(split(&input, " ", usize::MAX), vec!["a b"]);
+    }
+
+    /// Read end of a pipe to which `input` is written.
+    fn pipe(input: &[u8]) -> RawFd {
+        let mut fds = [0; 2];
+        unsafe {
+            assert_eq!(libc::pipe(fds.as_mut_ptr()), 0);
+            libc::write(fds[1], input.as_ptr() as *const libc::c_void, input.len());
+            libc::close(fds[1]);
+        }
+        fds[0]
+    }
+
+    #[test]
+    fn read_input_test() {
+        let fd = pipe("a\\ b\\\nc\\\\\nréste\nx".as_bytes());
+        let read = |options: &Options| {
+            let (input, status) = read_input(fd, options).unwrap();
+            let line: String = input.iter().map(|&(c, _)| c).collect();
+            (line, input.iter().filter(|&&(_, escaped)| escaped).count(), status)
+        };
+
+        assert_eq!(read(&Options::default()), (String::from("a bc\\"), 2, 0));
+        let options = Options {
+            count: Some(2),
+            ..Options::default()
+        };
+        assert_eq!(read(&options), (String::from("ré"), 0, 0));
+        let options = Options {
+            delimiter: b't',
+            raw: true,
+            ..Options::default()
+        };
+        assert_eq!(read(&options), (String::from("s"), 0, 0));
+        assert_eq!(read(&Options::default()), (String::from("e"), 0, 0));
+        assert_eq!(read(&Options::default()), (String::from("x"), 0, 1));
+        assert_eq!(read(&Options::default()), (String::new(), 0, 1));
+        unsafe { libc::close(fd) };
+    }
+
+    #[test]
+    fn parse_options_test() {
+        let args: Vec<_> = [
+            "read", "-rs", "-p", "> ", "-d:", "-n3", "-t", "1.5", "x", "-y",
+        ]
+        .iter()
+        .map(|&arg| String::from(arg))
+        .collect();
+        assert_eq!(
+            parse_options(&args).unwrap(),
+            Options {
+                raw: true,
+                silent: true,
+                array: None,
+                delimiter: b':',
+                count: Some(3),
+                prompt: Some(String::from("> ")),
+                timeout: Some(1.5),
+                names: vec![String::from("x"), String::from("-y")],
+            }
+        );
+    }
+}
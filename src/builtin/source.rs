@@ -0,0 +1,150 @@
+use std::env;
+use std::path::PathBuf;
+
+use super::*;
+
+pub(super) struct Source;
+
+impl Builtin for Source {
+    fn name(&self) -> &'static str {
+        "source"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "source file [args...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run the commands of file, searched in $PATH if it has no slash, in the current shell."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(source(args, rush), stdio)
+    }
+}
+
+pub(super) struct Dot;
+
+impl Builtin for Dot {
+    fn name(&self) -> &'static str {
+        "."
+    }
+
+    fn synopsis(&self) -> &'static str {
+        ". file [args...]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run the commands of file in the current shell, as source does."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(source(args, rush), stdio)
+    }
+}
+
+pub(super) struct Return;
+
+impl Builtin for Return {
+    fn name(&self) -> &'static str {
+        "return"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "return [n]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Stop running a sourced file, with the exit status n or the one of the last command."
+    }
+
+    fn run(&self, rush: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        ExitStatus::of(return_(args, rush), stdio)
+    }
+}
+
+/// `source file [args...]`, or `. file [args...]`
+fn source(args: &[String], rush: &mut Rush) -> Result<i32> {
+    let name = args
+        .get(1)
+        .ok_or_else(|| format!("{}: filename argument required", args[0]))?;
+    let positional = if args.len() > 2 {
+        Some(args[2..].to_vec())
+    } else {
+        None
+    };
+
+    rush.source(&find(name), positional)
+        .map_err(|e| format!("{}: {}: {}", args[0], name, e))
+}
+
+/// Path of the file `name`, searched in `$PATH` if it has no slash.
+fn find(name: &str) -> PathBuf {
+    if !name.contains('/') {
+        if let Some(paths) = env::var_os("PATH") {
+            if let Some(path) = env::split_paths(&paths)
+                .map(|dir| dir.join(name))
+                .find(|path| path.is_file())
+            {
+                return path;
+            }
+        }
+    }
+
+    PathBuf::from(name)
+}
+
+/// `return [n]`
+fn return_(args: &[String], rush: &mut Rush) -> Result<i32> {
+    let status = match args.get(1) {
+        Some(n) => n
+            .parse()
+            .map_err(|_| format!("return: {}: numeric argument required", n))?,
+        None => rush.params.status,
+    };
+
+    if !rush.return_from_source() {
+        return Err(String::from("return: can only `return' from a sourced file"));
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn source_test() {
+        let path = env::temp_dir().join(format!("rush-source-test-{}", process::id()));
+        fs::write(&path, "return $1\nexit 1\n").unwrap();
+        let x = vec![String::from("x")];
+        let run = |script: &str| {
+            let script = script.replace("FILE", path.to_str().unwrap());
+            let mut rush = Rush::with_command(&script, String::from("rush"), x.clone());
+            let status = rush.repl();
+            (status, rush.params.positional)
+        };
+
+        // The arguments are the positional parameters of the file only.
+        assert_eq!(run(". FILE 5 b"), (5, x.clone()));
+        assert_eq!(run("source FILE 5\nexit $?"), (5, x.clone()));
+        // `return` stops the file, not the shell.
+        assert_eq!(run(". FILE 5\nexit 7"), (7, x.clone()));
+
+        // The file sees the history of the shell.
+        fs::write(&path, "history -d 1\n").unwrap();
+        let mut rush = Rush::with_command("", String::from("rush"), vec![]);
+        let config = ::history::Config::from_env();
+        rush.history.add("echo a", &config);
+        rush.history.add("echo b", &config);
+        assert_eq!(rush.source(&path, None).unwrap(), 0);
+        assert_eq!(rush.history.entries(), ["echo b"]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(run("return 3\nexit $?").0, 1);
+        assert_eq!(run(". FILE\nexit $?").0, 1);
+        assert_eq!(run(".").0, 1);
+    }
+}
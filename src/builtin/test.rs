@@ -0,0 +1,532 @@
+use std::{ffi, fs};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+use libc;
+
+use super::*;
+
+const UNARY: &[&str] = &[
+    "-e", "-f", "-d", "-r", "-w", "-x", "-s", "-L", "-h", "-b", "-c", "-p", "-S", "-n", "-z",
+];
+const BINARY: &[&str] = &[
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+pub(super) struct Test;
+
+impl Builtin for Test {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "test expression"
+    }
+
+    fn help(&self) -> &'static str {
+        "Evaluate the conditional expression, with the exit status 0 if true and 1 if false."
+    }
+
+    fn takes_help_option(&self) -> bool {
+        false
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        test(args, stdio)
+    }
+}
+
+pub(super) struct Bracket;
+
+impl Builtin for Bracket {
+    fn name(&self) -> &'static str {
+        "["
+    }
+
+    fn synopsis(&self) -> &'static str {
+        "[ expression ]"
+    }
+
+    fn help(&self) -> &'static str {
+        "Evaluate the conditional expression as test does. The last argument must be ]."
+    }
+
+    fn takes_help_option(&self) -> bool {
+        false
+    }
+
+    fn run(&self, _: &mut Rush, args: &[String], stdio: &mut Stdio) -> ExitStatus {
+        test(args, stdio)
+    }
+}
+
+/// `test expression` or `[ expression ]`
+///
+/// Expressions are combined by `!`, `-a`, `-o` and parentheses.
+/// Returns 0 if the expression is true, 1 if false, and 2 on a syntax error.
+fn test(args: &[String], stdio: &mut Stdio) -> ExitStatus {
+    let name = args[0].as_str();
+    let mut words = &args[1..];
+    if name == "[" {
+        match words.split_last() {
+            Some((last, rest)) if last == "]" => words = rest,
+            _ => {
+                stdio.error("[: missing `]'");
+                return ExitStatus(2);
+            }
+        }
+    }
+
+    let mut expression = Expression::new(name, words, false);
+    expression.evaluate(stdio)
+}
+
+/// `[[ expression ]]`, where the words are escaped as by the lexer.
+///
+/// Expressions are combined by `!`, `&&`, `||` and parentheses.
+/// The right-hand side of `==` and `!=` is a glob pattern, and the one of `=~` is an extended
+/// regular expression, whose match and groups are stored in `BASH_REMATCH`.
+/// Quoted characters match literally in both.
+pub(super) fn conditional(rush: &mut Rush, words: &[String], stdio: &mut Stdio) -> ExitStatus {
+    let mut expression = Expression::new("[[", words, true);
+    let status = expression.evaluate(stdio);
+    if let Some(rematch) = expression.rematch {
+        rush.params
+            .arrays
+            .insert(String::from("BASH_REMATCH"), rematch);
+    }
+    status
+}
+
+/// A parser of an expression, which is evaluated once parsed, so that the operands of `&&` and
+/// `||` are evaluated only when needed.
+struct Expression<'a> {
+    name: &'a str,
+    words: &'a [String],
+    pos: usize,
+    /// Whether in `[[ ... ]]`.
+    conditional: bool,
+    /// `BASH_REMATCH` set by `=~`.
+    rematch: Option<Vec<String>>,
+}
+
+/// A parsed expression, referring to the words.
+enum Node<'a> {
+    Not(Box<Node<'a>>),
+    And(Box<Node<'a>>, Box<Node<'a>>),
+    Or(Box<Node<'a>>, Box<Node<'a>>),
+    /// A string, true if not empty.
+    String(&'a str),
+    Unary(&'a str, &'a str),
+    Binary(&'a str, &'a str, &'a str),
+}
+
+impl<'a> Expression<'a> {
+    fn new(name: &'a str, words: &'a [String], conditional: bool) -> Self {
+        Self {
+            name,
+            words,
+            pos: 0,
+            conditional,
+            rematch: None,
+        }
+    }
+
+    /// Parse and evaluate the expression, reporting a syntax error to `stdio`.
+    fn evaluate(&mut self, stdio: &mut Stdio) -> ExitStatus {
+        if self.words.is_empty() {
+            return ExitStatus::FAILURE;
+        }
+
+        let result = self.or().and_then(|node| match self.peek() {
+            None => self.eval(&node),
+            Some(word) => Err(format!("{}: unexpected argument", word)),
+        });
+        match result {
+            Ok(true) => ExitStatus::SUCCESS,
+            Ok(false) => ExitStatus::FAILURE,
+            Err(message) => {
+                stdio.error(format_args!("{}: {}", self.name, message));
+                ExitStatus(2)
+            }
+        }
+    }
+
+    fn eval(&mut self, node: &Node) -> ::std::result::Result<bool, String> {
+        match *node {
+            Node::Not(ref node) => self.eval(node).map(|result| !result),
+            Node::And(ref left, ref right) => Ok(self.eval(left)? && self.eval(right)?),
+            Node::Or(ref left, ref right) => Ok(self.eval(left)? || self.eval(right)?),
+            Node::String(word) => Ok(!self.operand(word).is_empty()),
+            Node::Unary(operator, operand) => unary(operator, &self.operand(operand)),
+            Node::Binary(operator, left, right) => {
+                let left = self.operand(left);
+                self.binary(operator, &left, right)
+            }
+        }
+    }
+
+    fn or(&mut self) -> ::std::result::Result<Node<'a>, String> {
+        let or = if self.conditional { "||" } else { "-o" };
+        let mut node = self.and()?;
+        while self.eat(or) {
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> ::std::result::Result<Node<'a>, String> {
+        let and = if self.conditional { "&&" } else { "-a" };
+        let mut node = self.not()?;
+        while self.eat(and) {
+            node = Node::And(Box::new(node), Box::new(self.not()?));
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> ::std::result::Result<Node<'a>, String> {
+        // A `!` as the last word is a string to test.
+        if self.peek() == Some("!") && self.pos + 1 < self.words.len() {
+            self.pos += 1;
+            return self.not().map(|node| Node::Not(Box::new(node)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> ::std::result::Result<Node<'a>, String> {
+        let word = match self.peek() {
+            Some(word) => word,
+            None => return Err(String::from("argument expected")),
+        };
+
+        if word == "(" && self.pos + 1 < self.words.len() {
+            self.pos += 1;
+            let node = self.or()?;
+            if !self.eat(")") {
+                return Err(String::from("`)' expected"));
+            }
+            return Ok(node);
+        }
+
+        let next = self.words.get(self.pos + 1).map(String::as_str);
+        if let Some(operator) = next {
+            if (BINARY.contains(&operator) || self.conditional && operator == "=~")
+                && self.pos + 2 < self.words.len()
+            {
+                let right = &self.words[self.pos + 2];
+                self.pos += 3;
+                return Ok(Node::Binary(operator, word, right));
+            }
+            if UNARY.contains(&word) {
+                self.pos += 2;
+                return Ok(Node::Unary(word, operator));
+            }
+        }
+
+        self.pos += 1;
+        Ok(Node::String(word))
+    }
+
+    fn binary(
+        &mut self,
+        operator: &str,
+        left: &str,
+        right: &str,
+    ) -> ::std::result::Result<bool, String> {
+        Ok(match operator {
+            "=" | "==" if self.conditional => glob_match(right, left),
+            "!=" if self.conditional => !glob_match(right, left),
+            "=~" => {
+                let groups = regex_match(&regex(right), left)?;
+                let matched = groups.is_some();
+                self.rematch = Some(groups.unwrap_or_default());
+                matched
+            }
+            _ => {
+                let right = self.operand(right);
+                match operator {
+                    "=" | "==" => left == right,
+                    "!=" => left != right,
+                    "<" => left < right.as_str(),
+                    ">" => left > right.as_str(),
+                    "-nt" | "-ot" => {
+                        let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified());
+                        match (modified(left), modified(&right)) {
+                            (Ok(left), Ok(right)) if operator == "-nt" => left > right,
+                            (Ok(left), Ok(right)) => left < right,
+                            (Ok(_), Err(_)) => operator == "-nt",
+                            (Err(_), Ok(_)) => operator == "-ot",
+                            (Err(_), Err(_)) => false,
+                        }
+                    }
+                    "-ef" => match (fs::metadata(left), fs::metadata(&right)) {
+                        (Ok(left), Ok(right)) => {
+                            left.dev() == right.dev() && left.ino() == right.ino()
+                        }
+                        _ => false,
+                    },
+                    _ => {
+                        let (left, right) = (integer(left)?, integer(&right)?);
+                        match operator {
+                            "-eq" => left == right,
+                            "-ne" => left != right,
+                            "-lt" => left < right,
+                            "-le" => left <= right,
+                            "-gt" => left > right,
+                            _ => left >= right,
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// The value of an operand, with escapes removed in `[[ ... ]]`.
+    fn operand(&self, word: &str) -> String {
+        if self.conditional {
+            unescape(word)
+        } else {
+            word.to_owned()
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.pos).map(String::as_str)
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        if self.peek() == Some(word) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn unary(operator: &str, operand: &str) -> ::std::result::Result<bool, String> {
+    let metadata = fs::metadata(operand);
+    Ok(match operator {
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-e" => metadata.is_ok(),
+        "-f" => metadata.is_ok_and(|m| m.is_file()),
+        "-d" => metadata.is_ok_and(|m| m.is_dir()),
+        "-s" => metadata.is_ok_and(|m| m.len() > 0),
+        "-b" => metadata.is_ok_and(|m| m.file_type().is_block_device()),
+        "-c" => metadata.is_ok_and(|m| m.file_type().is_char_device()),
+        "-p" => metadata.is_ok_and(|m| m.file_type().is_fifo()),
+        "-S" => metadata.is_ok_and(|m| m.file_type().is_socket()),
+        "-L" | "-h" => fs::symlink_metadata(operand).is_ok_and(|m| m.file_type().is_symlink()),
+        "-r" => access(operand, libc::R_OK),
+        "-w" => access(operand, libc::W_OK),
+        "-x" => access(operand, libc::X_OK),
+        _ => return Err(format!("{}: unary operator expected", operator)),
+    })
+}
+
+fn access(path: &str, mode: libc::c_int) -> bool {
+    match ffi::CString::new(ffi::OsStr::new(path).as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+fn integer(word: &str) -> ::std::result::Result<i64, String> {
+    word.trim()
+        .parse()
+        .map_err(|_| format!("{}: integer expression expected", word))
+}
+
+/// Remove the escapes by `\`.
+fn unescape(word: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Whether `string` matches the glob `pattern`, in which `\` escapes a character.
+fn glob_match(pattern: &str, string: &str) -> bool {
+    match (ffi::CString::new(pattern), ffi::CString::new(string)) {
+        (Ok(pattern), Ok(string)) => unsafe {
+            libc::fnmatch(pattern.as_ptr(), string.as_ptr(), 0) == 0
+        },
+        _ => false,
+    }
+}
+
+/// Convert the escaped `word` to an extended regular expression, keeping escapes only of the
+/// characters special in it.
+fn regex(word: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(c) if ".[]()*+?{}|^$\\".contains(c) => {
+                    regex.push('\\');
+                    regex.push(c);
+                }
+                Some(c) => regex.push(c),
+                None => regex.push('\\'),
+            },
+            c => regex.push(c),
+        }
+    }
+    regex
+}
+
+/// Match `string` against the extended regular expression `regex`, returning the match and
+/// its groups if matched.
+fn regex_match(regex: &str, string: &str) -> ::std::result::Result<Option<Vec<String>>, String> {
+    let invalid = || format!("{}: invalid regular expression", regex);
+    let c_regex = ffi::CString::new(regex).map_err(|_| invalid())?;
+    let c_string = match ffi::CString::new(string) {
+        Ok(string) => string,
+        Err(_) => return Ok(None),
+    };
+
+    unsafe {
+        let mut compiled: libc::regex_t = ::std::mem::zeroed();
+        if libc::regcomp(&mut compiled, c_regex.as_ptr(), libc::REG_EXTENDED) != 0 {
+            return Err(invalid());
+        }
+
+        let count = group_count(regex) + 1;
+        let mut matches = vec![
+            libc::regmatch_t {
+                rm_so: -1,
+                rm_eo: -1,
+            };
+            count
+        ];
+        let status = libc::regexec(&compiled, c_string.as_ptr(), count, matches.as_mut_ptr(), 0);
+        libc::regfree(&mut compiled);
+        if status != 0 {
+            return Ok(None);
+        }
+
+        let groups = matches
+            .iter()
+            .map(|m| match (m.rm_so, m.rm_eo) {
+                (start, end) if start >= 0 => string
+                    .get(start as usize..end as usize)
+                    .unwrap_or_default()
+                    .to_owned(),
+                _ => String::new(),
+            })
+            .collect();
+        Ok(Some(groups))
+    }
+}
+
+/// Number of groups in the extended regular expression `regex`.
+fn group_count(regex: &str) -> usize {
+    let mut count = 0;
+    let mut chars = regex.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '(' => count += 1,
+            '[' => {
+                // `]` right after `[` or `[^` is a member of the bracket expression.
+                chars.next_if_eq(&'^');
+                chars.next_if_eq(&']');
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn evaluate(words: &[&str], conditional: bool) -> i32 {
+        let words: Vec<_> = words.iter().map(|&word| String::from(word)).collect();
+        let (mut stdout, mut stderr) = (io::sink(), io::sink());
+        let mut stdio = Stdio::new(0, &mut stdout, &mut stderr, None);
+        Expression::new("test", &words, conditional).evaluate(&mut stdio).0
+    }
+
+    #[test]
+    fn test_test() {
+        assert_eq!(evaluate(&[], false), 1);
+        assert_eq!(evaluate(&["x"], false), 0);
+        assert_eq!(evaluate(&[""], false), 1);
+        assert_eq!(evaluate(&["-n"], false), 0);
+        assert_eq!(evaluate(&["!"], false), 0);
+        assert_eq!(evaluate(&["!", "x"], false), 1);
+        assert_eq!(evaluate(&["-z", ""], false), 0);
+        assert_eq!(evaluate(&["a", "=", "a", "-a", "1", "-lt", "2"], false), 0);
+        assert_eq!(
+            evaluate(&["a", "!=", "a", "-o", "(", "b", "<", "c", ")"], false),
+            0
+        );
+        assert_eq!(evaluate(&["!", "(", "-d", "/", ")"], false), 1);
+        assert_eq!(
+            evaluate(&["-f", "Cargo.toml", "-a", "-s", "Cargo.toml"], false),
+            0
+        );
+        assert_eq!(evaluate(&["-e", "no-such-file-rush"], false), 1);
+        assert_eq!(evaluate(&["a*", "=", "abc"], false), 1);
+        assert_eq!(evaluate(&["1", "-eq", "x"], false), 2);
+        assert_eq!(evaluate(&["(", "x"], false), 2);
+        assert_eq!(evaluate(&["a", "b"], false), 2);
+    }
+
+    #[test]
+    fn conditional_test() {
+        assert_eq!(evaluate(&["abc", "==", "a*"], true), 0);
+        assert_eq!(evaluate(&["abc", "==", "a\\*"], true), 1);
+        assert_eq!(evaluate(&["a*", "==", "a\\*"], true), 0);
+        assert_eq!(evaluate(&["x", "&&", "(", "", "||", "\\!", ")"], true), 0);
+        assert_eq!(evaluate(&["a", "-a", "b"], true), 2);
+        assert_eq!(evaluate(&["-n", "", "&&", "x", "=~", "("], true), 1);
+        assert_eq!(evaluate(&["x", "||", "x", "=~", "("], true), 0);
+        assert_eq!(evaluate(&["x", "||", "(", "y"], true), 2);
+
+        let words: Vec<_> = ["a", "=~", "a", "||", "b", "=~", "b"]
+            .iter()
+            .map(|&word| String::from(word))
+            .collect();
+        let (mut stdout, mut stderr) = (io::sink(), io::sink());
+        let mut stdio = Stdio::new(0, &mut stdout, &mut stderr, None);
+        let mut expression = Expression::new("[[", &words, true);
+        assert_eq!(expression.evaluate(&mut stdio), ExitStatus::SUCCESS);
+        assert_eq!(expression.rematch, Some(vec![String::from("a")]));
+    }
+
+    #[test]
+    fn regex_test() {
+        assert_eq!(regex("a\\.b\\ c\\<"), "a\\.b c<");
+        assert_eq!(
+            regex_match("([a-z]+)-([0-9]+)?", "x abc-12").unwrap(),
+            Some(vec![
+                String::from("abc-12"),
+                String::from("abc"),
+                String::from("12"),
+            ])
+        );
+        assert_eq!(regex_match("^b", "abc").unwrap(), None);
+        assert!(regex_match("(", "").is_err());
+        assert_eq!(group_count("(a)\\([(]()[]()]"), 2);
+    }
+}
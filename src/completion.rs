@@ -0,0 +1,583 @@
+//! Tab completion of the line editor.
+//!
+//! The word before the cursor is completed according to its position in the job:
+//!
+//! * a command name, by aliases, builtins and executables in `$PATH`, or by file names if it has
+//!   a `/`,
+//! * `$name` or `${name`, by variable names,
+//! * `~user`, by user names,
+//! * otherwise, by file names.
+//!
+//! Completed words are quoted in the same way as the word being typed.
+//!
+//! The arguments of a command with a `Spec` registered by the `complete` builtin are completed
+//! by the spec instead, falling back to the above if the spec has the `default` option.
+
+use std::{env, ffi, fmt, fs, process};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use libc;
+use rustyline;
+
+use builtin::Registry;
+use params::{self, Aliases};
+use parser::{self, WordPosition};
+
+/// Completion specs by command name.
+pub(super) type Specs = HashMap<String, Spec>;
+
+/// Completer of the line editor.
+pub(super) struct Completer {
+    specs: Rc<RefCell<Specs>>,
+    aliases: Rc<RefCell<Aliases>>,
+    builtins: Rc<Registry>,
+}
+
+/// How to complete the arguments of a command.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct Spec {
+    pub(super) actions: Vec<Action>,
+    /// Whether to fall back to the default completion if the actions generate no candidates.
+    pub(super) default: bool,
+}
+
+/// A way to generate candidates of a spec.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Action {
+    Files,
+    Directories,
+    Commands,
+    Variables,
+    Users,
+    Builtins,
+    Aliases,
+    /// Words in the list, separated by blanks.
+    Words(String),
+    /// Lines printed by the command, run by `rush -c` with the command name, the word being
+    /// completed and the previous word as `$1`, `$2` and `$3`.
+    Command(String),
+}
+
+/// Candidate of completion, before quoted.
+#[derive(Debug, PartialEq)]
+pub(super) struct Candidate {
+    pub(super) text: String,
+    /// Whether the word is finished, so that the quote is closed and a space follows.
+    /// Directories are not, to continue with the files in them.
+    finished: bool,
+}
+
+impl Completer {
+    pub(super) fn new(
+        specs: Rc<RefCell<Specs>>,
+        aliases: Rc<RefCell<Aliases>>,
+        builtins: Rc<Registry>,
+    ) -> Self {
+        Completer {
+            specs,
+            aliases,
+            builtins,
+        }
+    }
+}
+
+impl rustyline::completion::Completer for Completer {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let word = parser::partial_word(line);
+
+        if let Some(start) = line.rfind('$') {
+            if start >= word.start && word.quote != Some('\'') && line.ends_with(&word.value) {
+                if let Some(candidates) = variables(&line[start..]) {
+                    return Ok((start, candidates));
+                }
+            }
+        }
+
+        let spec = match word.command {
+            Some(ref command) if word.position == WordPosition::Argument => {
+                self.specs.borrow().get(command).cloned()
+            }
+            _ => None,
+        };
+        let generated = spec.map(|spec| {
+            let candidates = spec.generate(
+                word.command.as_ref().map_or("", String::as_str),
+                &word.value,
+                &word.previous,
+                &self.aliases.borrow(),
+                &self.builtins,
+            );
+            (candidates, spec.default)
+        });
+
+        let candidates = match generated {
+            Some((candidates, default)) if !(candidates.is_empty() && default) => candidates,
+            _ if word.quote.is_none() && is_user_prefix(&word.value) => users(&word.value[1..]),
+            _ if word.position == WordPosition::Command && !word.value.contains('/') => {
+                commands(&word.value, &self.aliases.borrow(), &self.builtins)
+            }
+            _ => files(&word.value),
+        };
+
+        Ok((
+            word.start,
+            candidates
+                .iter()
+                .map(|candidate| quote(candidate, word.quote))
+                .collect(),
+        ))
+    }
+}
+
+impl Spec {
+    /// Generate candidates for `word`, an argument of `command` after `previous`.
+    pub(super) fn generate(
+        &self,
+        command: &str,
+        word: &str,
+        previous: &str,
+        aliases: &Aliases,
+        builtins: &Registry,
+    ) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        for action in &self.actions {
+            match *action {
+                Action::Files => candidates.extend(files(word)),
+                Action::Directories => {
+                    candidates.extend(files(word).into_iter().filter(|c| !c.finished))
+                }
+                Action::Commands => candidates.extend(commands(word, aliases, builtins)),
+                Action::Variables => candidates.extend(finished(variable_names(word))),
+                Action::Users => candidates.extend(finished(user_names(word))),
+                Action::Builtins => candidates.extend(finished(
+                    builtins
+                        .names()
+                        .filter(|name| name.starts_with(word))
+                        .map(str::to_owned),
+                )),
+                Action::Aliases => candidates.extend(finished(alias_names(word, aliases))),
+                Action::Words(ref list) => candidates.extend(finished(
+                    list.split_whitespace()
+                        .filter(|w| w.starts_with(word))
+                        .map(str::to_owned),
+                )),
+                Action::Command(ref command_line) => {
+                    let output = env::current_exe().and_then(|rush| {
+                        process::Command::new(rush)
+                            .args(["-c", command_line, "rush", command, word, previous])
+                            .stdin(process::Stdio::null())
+                            .stderr(process::Stdio::null())
+                            .output()
+                    });
+                    if let Ok(output) = output {
+                        let output = String::from_utf8_lossy(&output.stdout);
+                        candidates.extend(finished(output.lines().map(str::to_owned)));
+                    }
+                }
+            }
+        }
+        candidates
+    }
+}
+
+impl fmt::Display for Spec {
+    /// Format as the options of `complete`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut options = vec![];
+        if self.default {
+            options.push(String::from("-o default"));
+        }
+        for action in &self.actions {
+            options.push(match *action {
+                Action::Files => String::from("-f"),
+                Action::Directories => String::from("-d"),
+                Action::Commands => String::from("-c"),
+                Action::Variables => String::from("-v"),
+                Action::Users => String::from("-u"),
+                Action::Builtins => String::from("-b"),
+                Action::Aliases => String::from("-a"),
+                Action::Words(ref list) => format!("-W {}", single_quote(list)),
+                Action::Command(ref command) => format!("-C {}", single_quote(command)),
+            });
+        }
+        write!(f, "{}", options.join(" "))
+    }
+}
+
+fn finished<I: IntoIterator<Item = String>>(texts: I) -> Vec<Candidate> {
+    texts
+        .into_iter()
+        .map(|text| Candidate {
+            text,
+            finished: true,
+        })
+        .collect()
+}
+
+/// Complete `$name` or `${name` in `word`.
+/// Returns `None` if `word` is not a parameter.
+fn variables(word: &str) -> Option<Vec<String>> {
+    let (open, prefix, close) = match word.strip_prefix("${") {
+        Some(prefix) => ("${", prefix, "}"),
+        None => ("$", &word[1..], ""),
+    };
+    if !prefix.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    Some(
+        variable_names(prefix)
+            .into_iter()
+            .map(|name| format!("{}{}{}", open, name, close))
+            .collect(),
+    )
+}
+
+fn variable_names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<_> = env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Whether `word` is `~user` to be completed by user names.
+fn is_user_prefix(word: &str) -> bool {
+    word.starts_with('~') && !word.contains('/')
+}
+
+/// Complete `~prefix` by the names of users starting with `prefix`.
+fn users(prefix: &str) -> Vec<Candidate> {
+    user_names(prefix)
+        .into_iter()
+        .map(|name| Candidate {
+            text: format!("~{}/", name),
+            finished: false,
+        })
+        .collect()
+}
+
+fn user_names(prefix: &str) -> Vec<String> {
+    let mut names = vec![];
+    unsafe {
+        libc::setpwent();
+        loop {
+            let entry = libc::getpwent();
+            if entry.is_null() {
+                break;
+            }
+            let name = ffi::CStr::from_ptr((*entry).pw_name).to_string_lossy();
+            if name.starts_with(prefix) {
+                names.push(name.into_owned());
+            }
+        }
+        libc::endpwent();
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Names of aliases starting with `prefix`, sorted.
+fn alias_names(prefix: &str, aliases: &Aliases) -> Vec<String> {
+    let mut names: Vec<_> = aliases
+        .keys()
+        .filter(|name| name.starts_with(prefix))
+        .cloned()
+        .collect();
+    names.sort();
+    names
+}
+
+/// Complete a command name by aliases, builtins and executables in `$PATH`.
+fn commands(prefix: &str, aliases: &Aliases, builtins: &Registry) -> Vec<Candidate> {
+    finished(command_names(prefix, aliases, builtins))
+}
+
+/// Names of aliases, builtins and executables in `$PATH` starting with `prefix`, sorted.
+fn command_names(prefix: &str, aliases: &Aliases, builtins: &Registry) -> Vec<String> {
+    let mut names = alias_names(prefix, aliases);
+    names.extend(
+        builtins
+            .names()
+            .filter(|name| name.starts_with(prefix))
+            .map(str::to_owned),
+    );
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    for dir in env::split_paths(&path) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if name.starts_with(prefix) && is_executable(&entry.path()) {
+                names.push(name);
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Commands whose names are close to `name`, which is not found, at most three of them with
+/// the closest first.
+pub(super) fn similar_commands(name: &str, aliases: &Aliases, builtins: &Registry) -> Vec<String> {
+    let max_distance = if name.chars().count() <= 4 { 1 } else { 2 };
+    let mut similar: Vec<_> = command_names("", aliases, builtins)
+        .into_iter()
+        .map(|command| (distance(name, &command), command))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect();
+    similar.sort();
+    similar.into_iter().take(3).map(|(_, command)| command).collect()
+}
+
+/// Edit distance between `a` and `b`, where an insertion, a deletion, a substitution, or a
+/// transposition of adjacent characters counts as one.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+
+    // `rows[i][j]` is the distance between the first `i` characters of `a` and `j` of `b`.
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut d = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+pub(super) fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Complete a file name, where the directory may start with `~` or `~user`.
+/// Hidden files are completed only if the name starts with `.`.
+fn files(word: &str) -> Vec<Candidate> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => word.split_at(i + 1),
+        None => ("", word),
+    };
+
+    let path = if dir.is_empty() {
+        PathBuf::from(".")
+    } else if let Some(dir) = dir.strip_prefix('~') {
+        let (user, rest) = dir.split_at(dir.find('/').unwrap_or(dir.len()));
+        match params::home_dir(user) {
+            Some(home) => Path::new(&home).join(rest.trim_start_matches('/')),
+            None => return vec![],
+        }
+    } else {
+        PathBuf::from(dir)
+    };
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut candidates: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || name.starts_with('.') && !prefix.starts_with('.') {
+                return None;
+            }
+
+            let is_dir = fs::metadata(entry.path()).is_ok_and(|metadata| metadata.is_dir());
+            Some(Candidate {
+                text: format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }),
+                finished: !is_dir,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.text.cmp(&b.text));
+    candidates
+}
+
+/// Quote `candidate` in the same way as the word opened by `quote`.
+fn quote(candidate: &Candidate, quote: Option<char>) -> String {
+    let mut quoted = String::new();
+    match quote {
+        Some('\'') => {
+            quoted = single_quote(&candidate.text);
+            quoted.pop();
+        }
+        Some(quote) => {
+            quoted.push(quote);
+            for c in candidate.text.chars() {
+                if c == '"' || c == '\\' {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+        }
+        None => {
+            for (i, c) in candidate.text.chars().enumerate() {
+                if " \t\n'\"\\$|&;<>()#`!*?[]{}".contains(c) || c == '~' && i > 0 {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+        }
+    }
+
+    if candidate.finished {
+        quoted.extend(quote);
+        quoted.push(' ');
+    }
+    quoted
+}
+
+/// Quote `text` in single quotes.
+pub(super) fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(text: &str, finished: bool) -> Candidate {
+        Candidate {
+            text: String::from(text),
+            finished,
+        }
+    }
+
+    #[test]
+    fn variables_test() {
+        // The environment is shared by the tests running in parallel, so the variable is
+        // unique to this test and removed at the end.
+        env::set_var("RUSH_COMPLETION_VARIABLES_TEST", "1");
+
+        assert_eq!(
+            variables("$RUSH_COMPLETION_VARIABLES_T"),
+            Some(vec![String::from("$RUSH_COMPLETION_VARIABLES_TEST")])
+        );
+        assert_eq!(
+            variables("${RUSH_COMPLETION_VARIABLES_T"),
+            Some(vec![String::from("${RUSH_COMPLETION_VARIABLES_TEST}")])
+        );
+        assert_eq!(variables("$RUSH_COMPLETION_VARIABLES_TEST/"), None);
+
+        env::remove_var("RUSH_COMPLETION_VARIABLES_TEST");
+    }
+
+    #[test]
+    fn commands_test() {
+        let mut aliases = Aliases::new();
+        let builtins = Registry::default();
+        assert_eq!(commands("hist", &aliases, &builtins), vec![candidate("history", true)]);
+
+        aliases.insert(String::from("hi"), String::from("history"));
+        assert_eq!(
+            commands("hi", &aliases, &builtins),
+            vec![candidate("hi", true), candidate("history", true)]
+        );
+    }
+
+    #[test]
+    fn similar_commands_test() {
+        assert_eq!(distance("gti", "git"), 1);
+        assert_eq!(distance("histroy", "history"), 1);
+        assert_eq!(distance("pushd", "popd"), 3);
+        assert_eq!(distance("", "cd"), 2);
+
+        let aliases = Aliases::new();
+        let builtins = Registry::default();
+        assert_eq!(similar_commands("histroy", &aliases, &builtins)[0], "history");
+        assert!(similar_commands("no-such-command-rush", &aliases, &builtins).is_empty());
+    }
+
+    #[test]
+    fn files_test() {
+        let dir = env::temp_dir().join(format!("rush-files-test-{}", process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for file in &["Cargo.toml", ".hidden", "src/lib.rs", "src/main.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let path = |file| format!("{}/{}", dir.display(), file);
+
+        assert_eq!(files(&path("sr")), vec![candidate(&path("src/"), false)]);
+        assert_eq!(
+            files(&path("src/")),
+            vec![candidate(&path("src/lib.rs"), true), candidate(&path("src/main.rs"), true)]
+        );
+        assert_eq!(
+            files(&path("")),
+            vec![candidate(&path("Cargo.toml"), true), candidate(&path("src/"), false)]
+        );
+        assert_eq!(files(&path(".h")), vec![candidate(&path(".hidden"), true)]);
+        assert_eq!(files(&path("no-such-file")), vec![]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn spec_test() {
+        let mut spec = Spec {
+            actions: vec![
+                Action::Words(String::from("status stash push")),
+                Action::Builtins,
+            ],
+            default: true,
+        };
+        assert_eq!(
+            spec.generate("git", "s", "git", &Aliases::new(), &Registry::default()),
+            vec![
+                candidate("status", true),
+                candidate("stash", true),
+                candidate("source", true),
+            ]
+        );
+
+        spec.actions.push(Action::Command(String::from("echo $1 $2 $3")));
+        assert_eq!(
+            spec.to_string(),
+            "-o default -W 'status stash push' -b -C 'echo $1 $2 $3'"
+        );
+    }
+
+    #[test]
+    fn quote_test() {
+        assert_eq!(quote(&candidate("a b/", false), None), "a\\ b/");
+        assert_eq!(quote(&candidate("~x/$y~", true), None), "~x/\\$y\\~ ");
+        assert_eq!(quote(&candidate("it's", true), Some('\'')), "'it'\\''s' ");
+        assert_eq!(quote(&candidate("a\"b/", false), Some('"')), "\"a\\\"b/");
+    }
+}
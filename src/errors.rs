@@ -1,7 +1,8 @@
-use std::{io, result};
+use std::{fmt, io, result};
 
 use rustyline;
-use nom;
+
+use parser::ParseError;
 
 pub(super) type Result<T> = result::Result<T, Error>;
 
@@ -9,17 +10,36 @@ pub(super) type Result<T> = result::Result<T, Error>;
 pub(super) enum Error {
     // Read
     Read(rustyline::error::ReadlineError),
-    Eof,         // Ctrl-D
-    Interrupted, // Ctrl-C
+    Eof,             // Ctrl-D
+    Interrupted,     // Ctrl-C
+    History(String), // Failed history expansion
 
     // Parse
-    Parse(nom::IError<u32>),
+    Parse(ParseError),
+    Incomplete, // More input is needed to complete the job
 
     // Execute
-    Builtin(String),
+    CommandNotFound(String), // Not found in `$PATH`
+    Exec(String, io::Error), // Failed to execute a command
     IO(io::Error),
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Read(ref e) => write!(f, "{}", e),
+            Error::Eof => write!(f, "EOF"),
+            Error::Interrupted => write!(f, "Interrupted"),
+            Error::History(ref e) => write!(f, "{}", e),
+            Error::Parse(ref e) => write!(f, "{}", e),
+            Error::Incomplete => write!(f, "syntax error: unexpected end of file"),
+            Error::CommandNotFound(ref name) => write!(f, "{}: command not found", name),
+            Error::Exec(ref name, ref e) => write!(f, "{}: {}", name, e),
+            Error::IO(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
 impl From<rustyline::error::ReadlineError> for Error {
     fn from(e: rustyline::error::ReadlineError) -> Self {
         use rustyline::error::ReadlineError::{Eof, Interrupted};
@@ -32,12 +52,6 @@ impl From<rustyline::error::ReadlineError> for Error {
     }
 }
 
-impl From<nom::IError> for Error {
-    fn from(e: nom::IError) -> Self {
-        Error::Parse(e)
-    }
-}
-
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Self {
         Error::IO(e)
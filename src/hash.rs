@@ -0,0 +1,124 @@
+//! Paths of commands found in `$PATH`, remembered so that `$PATH` is not searched on every run.
+//!
+//! The table is cleared when `$PATH` changes, and an entry is searched again when its file is
+//! no longer executable, unless it is given by `hash -p`.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use completion;
+
+#[derive(Debug, Default)]
+pub(super) struct CommandHash {
+    /// `$PATH` when the entries were searched.
+    path: Option<OsString>,
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug)]
+pub(super) struct Entry {
+    pub(super) path: PathBuf,
+    /// Number of times the command has been run by the path.
+    pub(super) hits: usize,
+    /// Whether the path is given by `hash -p`, so it is run even if not executable.
+    pinned: bool,
+}
+
+impl CommandHash {
+    /// Path of the command `name`, searched in `$PATH` if not remembered, counting a hit.
+    pub(super) fn find(&mut self, name: &str) -> Option<&Path> {
+        let entry = self.entry(name)?;
+        entry.hits += 1;
+        Some(&entry.path)
+    }
+
+    /// Search `name` in `$PATH` and remember it without counting a hit.
+    /// Returns whether it is found.
+    pub(super) fn add(&mut self, name: &str) -> bool {
+        self.entry(name).is_some()
+    }
+
+    /// Remember `path` as the path of `name`, whether or not it is in `$PATH`.
+    pub(super) fn insert(&mut self, name: &str, path: PathBuf) {
+        self.sync_path();
+        let entry = Entry { path, hits: 0, pinned: true };
+        self.entries.insert(name.to_owned(), entry);
+    }
+
+    /// Path of `name` if remembered and `$PATH` is not changed since.
+    pub(super) fn get(&self, name: &str) -> Option<&Path> {
+        if self.path != env::var_os("PATH") {
+            return None;
+        }
+        self.entries.get(name).map(|entry| entry.path.as_path())
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Remembered commands, sorted by name.
+    pub(super) fn entries(&mut self) -> Vec<(&String, &Entry)> {
+        self.sync_path();
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    fn entry(&mut self, name: &str) -> Option<&mut Entry> {
+        self.sync_path();
+        let stale = match self.entries.get(name) {
+            Some(entry) => !entry.pinned && !completion::is_executable(&entry.path),
+            None => true,
+        };
+        if stale {
+            self.entries.remove(name);
+            let path = search(name, self.path.as_ref())?;
+            let entry = Entry { path, hits: 0, pinned: false };
+            self.entries.insert(name.to_owned(), entry);
+        }
+        self.entries.get_mut(name)
+    }
+
+    /// Forget the entries if `$PATH` is changed.
+    fn sync_path(&mut self) {
+        let path = env::var_os("PATH");
+        if self.path != path {
+            self.entries.clear();
+            self.path = path;
+        }
+    }
+}
+
+/// Path of the first executable `name` in the directories of `path`.
+fn search(name: &str, path: Option<&OsString>) -> Option<PathBuf> {
+    env::split_paths(path?)
+        .map(|dir| dir.join(name))
+        .find(|path| completion::is_executable(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_test() {
+        let path = OsString::from("/no-such-dir-rush:src:.");
+        assert_eq!(search("Cargo.toml", Some(&path)), None);
+        assert_eq!(search("no-such-command-rush", Some(&path)), None);
+        assert_eq!(search("sh", Some(&OsString::from("/bin"))), Some(PathBuf::from("/bin/sh")));
+    }
+
+    #[test]
+    fn insert_test() {
+        let mut hash = CommandHash::default();
+        hash.insert("sh", PathBuf::from("Cargo.toml"));
+        assert_eq!(hash.find("sh"), Some(Path::new("Cargo.toml")));
+        assert_eq!(hash.entries()[0].1.hits, 1);
+
+        hash.clear();
+        assert_ne!(hash.find("sh"), Some(Path::new("Cargo.toml")));
+    }
+}
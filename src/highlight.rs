@@ -0,0 +1,126 @@
+//! Syntax highlighting of the line editor.
+//!
+//! Command names are green if they are found as aliases, builtins or executables, and red
+//! otherwise.
+//! Quoted strings are yellow, operators and redirections cyan, and hints from the history grey.
+
+use std::env;
+use std::path::Path;
+
+use builtin::Registry;
+use completion;
+use params::Aliases;
+use parser::{self, Highlight};
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const GREY: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+/// Color `line` with ANSI escape sequences.
+pub(super) fn highlight(line: &str, aliases: &Aliases, builtins: &Registry) -> String {
+    let mut highlighted = String::new();
+    let mut end = 0;
+
+    for (range, highlight) in parser::highlights(line) {
+        highlighted.push_str(&line[end..range.start]);
+        let text = &line[range.start..range.end];
+        match highlight {
+            Highlight::Command(ref name)
+                if aliases.contains_key(name) || builtins.contains(name) || is_command(name) =>
+            {
+                push_colored(&mut highlighted, GREEN, text)
+            }
+            Highlight::Command(_) => push_colored(&mut highlighted, RED, text),
+            Highlight::Word => push_strings(&mut highlighted, text),
+            Highlight::Operator => push_colored(&mut highlighted, CYAN, text),
+        }
+        end = range.end;
+    }
+
+    highlighted.push_str(&line[end..]);
+    highlighted
+}
+
+/// Color a hint shown after the line.
+pub(super) fn highlight_hint(hint: &str) -> String {
+    format!("{}{}{}", GREY, hint, RESET)
+}
+
+fn push_colored(highlighted: &mut String, color: &str, text: &str) {
+    highlighted.push_str(color);
+    highlighted.push_str(text);
+    highlighted.push_str(RESET);
+}
+
+/// Push `word`, coloring quoted strings in it.
+fn push_strings(highlighted: &mut String, word: &str) {
+    let mut chars = word.chars();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                highlighted.push_str(YELLOW);
+                highlighted.push(c);
+            }
+            (None, '\\') | (Some('"'), '\\') => {
+                highlighted.push(c);
+                highlighted.extend(chars.next());
+            }
+            (Some(q), c) if c == q => {
+                quote = None;
+                highlighted.push(c);
+                highlighted.push_str(RESET);
+            }
+            (_, c) => highlighted.push(c),
+        }
+    }
+
+    if quote.is_some() {
+        highlighted.push_str(RESET);
+    }
+}
+
+/// Whether `name` is an executable, in `$PATH` if it has no `/`.
+fn is_command(name: &str) -> bool {
+    if name.contains('/') {
+        return completion::is_executable(Path::new(name));
+    }
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path).any(|dir| completion::is_executable(&dir.join(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_test() {
+        let mut aliases = Aliases::new();
+        let builtins = Registry::default();
+        assert_eq!(
+            highlight("cd 'a b'\"c\" |", &aliases, &builtins),
+            "\x1b[32mcd\x1b[0m \x1b[33m'a b'\x1b[0m\x1b[33m\"c\"\x1b[0m \x1b[36m|\x1b[0m"
+        );
+        assert_eq!(
+            highlight("no-such-command-rush >x \\'a", &aliases, &builtins),
+            "\x1b[31mno-such-command-rush\x1b[0m \x1b[36m>\x1b[0mx \\'a"
+        );
+        assert_eq!(highlight("# comment", &aliases, &builtins), "# comment");
+        assert_eq!(
+            highlight("history \"a", &aliases, &builtins),
+            "\x1b[32mhistory\x1b[0m \x1b[33m\"a\x1b[0m"
+        );
+
+        aliases.insert(String::from("no-such-command-rush"), String::from("ls"));
+        assert_eq!(
+            highlight("no-such-command-rush", &aliases, &builtins),
+            "\x1b[32mno-such-command-rush\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! History expansion, as in bash.
+//!
+//! An event designator starting with `!` is replaced with an entry of the history:
+//!
+//! * `!!`: the previous entry,
+//! * `!n`: the entry numbered `n`, as listed by `history`,
+//! * `!-n`: the `n`-th entry from the end,
+//! * `!prefix`: the last entry starting with `prefix`,
+//! * `!?string?`: the last entry containing `string`.
+//!
+//! The event may be followed by a word designator after `:`, `0` for the command name, `n` for
+//! the `n`-th argument, `^` for the first one, `$` for the last one, `x-y` for a range and `*`
+//! for all the arguments. `x-` is `x-$` without the last word, and `x*` is `x-$`.
+//! `!$`, `!^` and `!*` are short for `!!:$`, `!!:^` and `!!:*`.
+//!
+//! Then follow modifiers, each after `:`:
+//!
+//! * `h`: remove the last pathname component, leaving the head,
+//! * `t`: remove all but the last pathname component, leaving the tail,
+//! * `r`: remove the trailing suffix `.xxx`,
+//! * `e`: remove all but the trailing suffix,
+//! * `s/old/new/`: replace the first `old` with `new`, where `&` in `new` stands for `old`,
+//!   `gs/old/new/` replacing all of them,
+//! * `p`: print the result without running it.
+//!
+//! `^old^new^` at the start of the line is short for `!!:s^old^new^`.
+//!
+//! `!` is not expanded in single quotes, after `\`, or before a blank, `=`, `(` or `"`.
+
+use parser;
+
+/// Line after history expansion.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Expanded {
+    pub(crate) line: String,
+    /// Whether the line is only printed, by the `p` modifier.
+    pub(crate) print_only: bool,
+}
+
+/// Perform history expansion on `line` with `history`.
+/// Returns `None` if nothing is expanded, or the error message if an expansion fails.
+pub(crate) fn expand(line: &str, history: &[String]) -> Result<Option<Expanded>, String> {
+    let quick;
+    let line = if line.starts_with('^') {
+        quick = format!("!!:s{}", line);
+        &quick
+    } else {
+        line
+    };
+    if !line.contains('!') {
+        return Ok(None);
+    }
+
+    let mut expander = Expander {
+        line,
+        history,
+        pos: 0,
+        expanded: false,
+        print_only: false,
+    };
+    let expanded = expander.run()?;
+
+    if expander.expanded {
+        Ok(Some(Expanded {
+            line: expanded,
+            print_only: expander.print_only,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+struct Expander<'a> {
+    line: &'a str,
+    history: &'a [String],
+    pos: usize,
+    expanded: bool,
+    print_only: bool,
+}
+
+impl<'a> Expander<'a> {
+    fn run(&mut self) -> Result<String, String> {
+        let mut output = String::new();
+        let mut single_quoted = false;
+        let mut double_quoted = false;
+
+        while let Some(c) = self.next() {
+            match c {
+                '\\' if !single_quoted => {
+                    output.push(c);
+                    if let Some(c) = self.next() {
+                        output.push(c);
+                    }
+                }
+                '\'' if !double_quoted => {
+                    single_quoted = !single_quoted;
+                    output.push(c);
+                }
+                '"' if !single_quoted => {
+                    double_quoted = !double_quoted;
+                    output.push(c);
+                }
+                '!' if !single_quoted && self.is_event() => {
+                    let start = self.pos - 1;
+                    let text = self.event(start)?;
+                    output.push_str(&text);
+                    self.expanded = true;
+                }
+                c => output.push(c),
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Whether the `!` just read starts an event designator.
+    fn is_event(&self) -> bool {
+        match self.peek() {
+            Some(c) => !(c.is_whitespace() || c == '=' || c == '(' || c == '"'),
+            None => false,
+        }
+    }
+
+    /// Expand the event designator starting at `start`, followed by the word designator and
+    /// modifiers.
+    fn event(&mut self, start: usize) -> Result<String, String> {
+        let entry = match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                self.history.last()
+            }
+            // Short for `!!` with a word designator.
+            Some('$') | Some('^') | Some('*') | Some(':') => self.history.last(),
+            Some('?') => {
+                self.pos += 1;
+                let string = self.take_while(|c| c != '?' && c != '\n');
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                }
+                self.history.iter().rev().find(|e| e.contains(string))
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' && self.is_number_at(1) => {
+                let negative = c == '-';
+                if negative {
+                    self.pos += 1;
+                }
+                match self.number() {
+                    Some(n) if negative => self
+                        .history
+                        .len()
+                        .checked_sub(n)
+                        .and_then(|i| self.history.get(i)),
+                    Some(n) => n.checked_sub(1).and_then(|i| self.history.get(i)),
+                    None => None,
+                }
+            }
+            _ => {
+                let prefix = self.take_while(|c| !(c.is_whitespace() || ":;&|<>()'\"".contains(c)));
+                self.history.iter().rev().find(|e| e.starts_with(prefix))
+            }
+        };
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Err(format!("{}: event not found", &self.line[start..self.pos])),
+        };
+
+        let mut text = match (self.peek(), self.peek_at(1)) {
+            (Some('$'), _) | (Some('^'), _) | (Some('*'), _) => self.words(entry, start)?,
+            (Some(':'), Some(c)) if c.is_ascii_digit() || "^$*-".contains(c) => {
+                self.pos += 1;
+                self.words(entry, start)?
+            }
+            _ => entry.clone(),
+        };
+
+        while self.peek() == Some(':') {
+            match self.peek_at(1) {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.pos += 1;
+                    self.modify(&mut text)?;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(text)
+    }
+
+    /// Select words of `entry` by the word designator.
+    fn words(&mut self, entry: &str, start: usize) -> Result<String, String> {
+        let words = parser::split_words(entry);
+        let len = words.len();
+        let last = len.checked_sub(1);
+
+        // Range of the words, excluding the end.
+        let range = match self.peek() {
+            Some('^') => {
+                self.pos += 1;
+                Some((1, 2))
+            }
+            Some('$') => {
+                self.pos += 1;
+                last.map(|last| (last, len))
+            }
+            Some('*') => {
+                self.pos += 1;
+                Some((1, len))
+            }
+            _ => {
+                let first = if self.peek() == Some('-') {
+                    Some(0)
+                } else {
+                    self.number()
+                };
+                match (first, self.peek()) {
+                    (Some(first), Some('*')) => {
+                        self.pos += 1;
+                        Some((first, len))
+                    }
+                    (Some(first), Some('-')) => {
+                        self.pos += 1;
+                        if self.peek() == Some('$') {
+                            self.pos += 1;
+                            Some((first, len))
+                        } else if self.is_number_at(0) {
+                            self.number()
+                                .filter(|&end| end >= first)
+                                .map(|end| (first, end + 1))
+                        } else {
+                            last.map(|last| (first, last))
+                        }
+                    }
+                    (Some(first), _) => Some((first, first + 1)),
+                    (None, _) => None,
+                }
+            }
+        };
+
+        match range {
+            Some((first, end)) if first <= end && end <= len => Ok(words[first..end].join(" ")),
+            _ => Err(format!(
+                "{}: bad word specifier",
+                &self.line[start..self.pos]
+            )),
+        }
+    }
+
+    /// Apply the modifier after `:` to `text`.
+    fn modify(&mut self, text: &mut String) -> Result<(), String> {
+        match self.next() {
+            Some('h') => {
+                if let Some(i) = text.rfind('/') {
+                    text.truncate(i);
+                }
+            }
+            Some('t') => {
+                if let Some(i) = text.rfind('/') {
+                    *text = text[i + 1..].to_owned();
+                }
+            }
+            Some('r') => {
+                if let Some(i) = suffix(text) {
+                    text.truncate(i);
+                }
+            }
+            Some('e') => {
+                *text = suffix(text).map_or_else(String::new, |i| text[i..].to_owned());
+            }
+            Some('p') => self.print_only = true,
+            Some('s') => self.substitute(text, false)?,
+            Some('g') if self.peek() == Some('s') => {
+                self.pos += 1;
+                self.substitute(text, true)?;
+            }
+            Some(c) => return Err(format!("{}: unrecognized history modifier", c)),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Apply `s/old/new/` after `s` to `text`, replacing all matches if `global`.
+    fn substitute(&mut self, text: &mut String, global: bool) -> Result<(), String> {
+        let start = self.pos - 1;
+        let delimiter = match self.next() {
+            Some(c) => c,
+            None => return Err(String::from("s: no previous substitution")),
+        };
+        let old = self.delimited(delimiter);
+        let new = self.delimited(delimiter);
+        let spec = &self.line[start..self.pos];
+
+        if old.is_empty() {
+            return Err(format!("{}: no previous substitution", spec));
+        }
+        if !text.contains(&old) {
+            return Err(format!("{}: substitution failed", spec));
+        }
+
+        let mut replacement = String::new();
+        let mut chars = new.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => replacement.extend(chars.next()),
+                '&' => replacement.push_str(&old),
+                c => replacement.push(c),
+            }
+        }
+
+        *text = if global {
+            text.replace(&old, &replacement)
+        } else {
+            text.replacen(&old, &replacement, 1)
+        };
+        Ok(())
+    }
+
+    /// Read a part of `s/old/new/` up to `delimiter` or the end of the line.
+    /// `\` escapes the delimiter, and is kept before other characters.
+    fn delimited(&mut self, delimiter: char) -> String {
+        let mut part = String::new();
+        while let Some(c) = self.next() {
+            match c {
+                c if c == delimiter => break,
+                '\\' if self.peek() == Some(delimiter) => {
+                    part.push(delimiter);
+                    self.pos += delimiter.len_utf8();
+                }
+                c => part.push(c),
+            }
+        }
+        part
+    }
+
+    fn take_while<P: Fn(char) -> bool>(&mut self, predicate: P) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.line[start..self.pos]
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        self.take_while(|c| c.is_ascii_digit()).parse().ok()
+    }
+
+    fn is_number_at(&self, n: usize) -> bool {
+        self.peek_at(n).is_some_and(|c| c.is_ascii_digit())
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, n: usize) -> Option<char> {
+        self.line[self.pos..].chars().nth(n)
+    }
+}
+
+/// Position of the `.` starting the suffix of the last pathname component of `text`.
+fn suffix(text: &str) -> Option<usize> {
+    let dot = text.rfind('.')?;
+    if text[dot..].contains('/') {
+        None
+    } else {
+        Some(dot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        vec![
+            String::from("ls -l /usr/lib"),
+            String::from("tar xf a/b/c.tar.gz"),
+            String::from("echo 'x y' z"),
+        ]
+    }
+
+    fn expanded(line: &str) -> String {
+        expand(line, &history()).unwrap().unwrap().line
+    }
+
+    fn error(line: &str) -> String {
+        expand(line, &history()).unwrap_err()
+    }
+
+    #[test]
+    fn event_test() {
+        assert_eq!(expanded("!!"), "echo 'x y' z");
+        assert_eq!(expanded("sudo !! | cat"), "sudo echo 'x y' z | cat");
+        assert_eq!(expanded("!1"), "ls -l /usr/lib");
+        assert_eq!(expanded("!-2"), "tar xf a/b/c.tar.gz");
+        assert_eq!(expanded("!l;!e"), "ls -l /usr/lib;echo 'x y' z");
+        assert_eq!(expanded("!?xf?"), "tar xf a/b/c.tar.gz");
+        assert_eq!(expanded("\"!ta\""), "\"tar xf a/b/c.tar.gz\"");
+
+        assert_eq!(expand("echo hi", &history()), Ok(None));
+        assert_eq!(expand("echo ! a!= !(x) '!!' \\!!", &history()), Ok(None));
+
+        assert_eq!(error("!4"), "!4: event not found");
+        assert_eq!(error("!-4 x"), "!-4: event not found");
+        assert_eq!(error("!cat"), "!cat: event not found");
+        assert_eq!(expand("!!", &[]), Err(String::from("!!: event not found")));
+    }
+
+    #[test]
+    fn word_test() {
+        assert_eq!(expanded("!!:0"), "echo");
+        assert_eq!(expanded("!!:1"), "'x y'");
+        assert_eq!(expanded("!$"), "z");
+        assert_eq!(expanded("!^"), "'x y'");
+        assert_eq!(expanded("!*"), "'x y' z");
+        assert_eq!(expanded("!l:1-2"), "-l /usr/lib");
+        assert_eq!(expanded("!l:-1"), "ls -l");
+        assert_eq!(expanded("!l:0-"), "ls -l");
+        assert_eq!(expanded("!l:1*"), "-l /usr/lib");
+        assert_eq!(expanded("!1:2-$"), "/usr/lib");
+        assert_eq!(expanded("!!:3*"), "");
+
+        assert_eq!(error("!!:4"), "!!:4: bad word specifier");
+        assert_eq!(error("!!:2-1"), "!!:2-1: bad word specifier");
+    }
+
+    #[test]
+    fn modifier_test() {
+        assert_eq!(expanded("!t:$:h"), "a/b");
+        assert_eq!(expanded("!t:$:t"), "c.tar.gz");
+        assert_eq!(expanded("!t:$:r"), "a/b/c.tar");
+        assert_eq!(expanded("!t:$:e"), ".gz");
+        assert_eq!(expanded("!t:$:t:r:r"), "c");
+        assert_eq!(expanded("!l:$:h:h"), "");
+        assert_eq!(expanded("!!:s/z/w/"), "echo 'x y' w");
+        assert_eq!(expanded("!t:gs/a/[&]"), "t[a]r xf [a]/b/c.t[a]r.gz");
+        assert_eq!(expanded("!t:s|/|\\||"), "tar xf a|b/c.tar.gz");
+        assert_eq!(expanded("!!:1:"), "'x y':");
+
+        assert_eq!(
+            expand("!l:p", &history()),
+            Ok(Some(Expanded {
+                line: String::from("ls -l /usr/lib"),
+                print_only: true,
+            }))
+        );
+
+        assert_eq!(error("!!:s/q/r/"), "s/q/r/: substitution failed");
+        assert_eq!(error("!!:x"), "x: unrecognized history modifier");
+    }
+
+    #[test]
+    fn quick_substitution_test() {
+        assert_eq!(expanded("^z^w"), "echo 'x y' w");
+        assert_eq!(expanded("^echo^printf^ %s"), "printf 'x y' z %s");
+        assert_eq!(error("^q^r"), "s^q^r: substitution failed");
+    }
+}
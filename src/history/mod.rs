@@ -0,0 +1,399 @@
+//! Command history, persisted to `$HISTFILE`.
+//!
+//! The history is configured by the variables
+//!
+//! * `HISTFILE`: path of the history file, `~/.rush_history` by default,
+//! * `HISTSIZE`: maximum number of entries kept in memory, 500 by default,
+//! * `HISTFILESIZE`: maximum number of entries kept in the file, `HISTSIZE` by default,
+//! * `HISTCONTROL`: colon-separated list of `ignorespace`, `ignoredups`, `ignoreboth` and
+//!   `erasedups`.
+//!
+//! A negative size means unlimited.
+//!
+//! In the file, each entry is written on a line, with `\` and newline escaped as `\\` and `\n`.
+//!
+//! Entries are recalled in interactive input by history expansion, see `expand`.
+
+use std::{env, fs, io};
+use std::io::{Read, Seek, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use libc;
+
+use params;
+
+mod expand;
+
+pub(super) use self::expand::expand;
+
+const DEFAULT_SIZE: usize = 500;
+
+#[derive(Debug, Default)]
+pub(super) struct History {
+    entries: Vec<String>,
+    /// Entries added since the last save, including the ones no longer in `entries`.
+    unsaved: Vec<String>,
+    /// Number of the last entries of `unsaved` which are also the last ones of `entries`.
+    pending: usize,
+}
+
+/// Configuration of the history given by the variables.
+#[derive(Debug)]
+pub(super) struct Config {
+    file: Option<PathBuf>,
+    pub(super) size: Option<usize>,
+    file_size: Option<usize>,
+    ignore_space: bool,
+    ignore_dups: bool,
+    pub(super) erase_dups: bool,
+}
+
+impl Config {
+    pub(super) fn from_env() -> Self {
+        let file = match env::var_os("HISTFILE") {
+            Some(ref file) if file.is_empty() => None,
+            Some(file) => Some(PathBuf::from(file)),
+            None => params::home_dir("").map(|home| Path::new(&home).join(".rush_history")),
+        };
+
+        let size = Self::parse_size(env::var("HISTSIZE").ok(), Some(DEFAULT_SIZE));
+        let file_size = Self::parse_size(env::var("HISTFILESIZE").ok(), size);
+
+        let control = env::var("HISTCONTROL").unwrap_or_default();
+        let control: Vec<_> = control.split(':').collect();
+        let ignore_both = control.contains(&"ignoreboth");
+
+        Self {
+            file,
+            size,
+            file_size,
+            ignore_space: ignore_both || control.contains(&"ignorespace"),
+            ignore_dups: ignore_both || control.contains(&"ignoredups"),
+            erase_dups: control.contains(&"erasedups"),
+        }
+    }
+
+    /// Parse a size, where `None` means unlimited.
+    fn parse_size(size: Option<String>, default: Option<usize>) -> Option<usize> {
+        match size.map(|size| size.parse::<i64>()) {
+            Some(Ok(size)) if size < 0 => None,
+            Some(Ok(size)) => Some(size as usize),
+            _ => default,
+        }
+    }
+}
+
+impl History {
+    pub(super) fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Add `line` to the history unless ignored by `config`.
+    /// Returns whether the line is added.
+    pub(super) fn add(&mut self, line: &str, config: &Config) -> bool {
+        if line.is_empty() || config.size == Some(0) {
+            return false;
+        }
+        if config.ignore_space && line.starts_with(' ') {
+            return false;
+        }
+        if config.ignore_dups && self.entries.last().is_some_and(|last| last == line) {
+            return false;
+        }
+
+        if config.erase_dups {
+            while let Some(index) = self.entries.iter().rposition(|entry| entry == line) {
+                self.remove(index);
+            }
+        }
+        self.entries.push(line.to_owned());
+        self.unsaved.push(line.to_owned());
+        self.pending += 1;
+        Self::truncate(&mut self.entries, config.size);
+        self.pending = self.pending.min(self.entries.len());
+
+        true
+    }
+
+    /// Delete the `index`-th entry, counted from 0.
+    /// Returns whether the entry exists.
+    pub(super) fn delete(&mut self, index: usize) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+
+        self.remove(index);
+        true
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+        self.unsaved.clear();
+        self.pending = 0;
+    }
+
+    /// Remove the `index`-th entry, and from `unsaved` too if it is not saved yet.
+    fn remove(&mut self, index: usize) {
+        let from_end = self.entries.len() - index;
+        self.entries.remove(index);
+        if from_end <= self.pending {
+            let unsaved = self.unsaved.len() - from_end;
+            self.unsaved.remove(unsaved);
+            self.pending -= 1;
+        }
+    }
+
+    /// Load entries from the history file.
+    pub(super) fn load(&mut self, config: &Config) -> io::Result<()> {
+        let path = match config.file {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        lock(&file, libc::LOCK_SH)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        self.entries = decode(&content);
+        Self::truncate(&mut self.entries, config.size);
+        self.pending = 0;
+
+        Ok(())
+    }
+
+    /// Append the entries added since the last save to the history file.
+    /// The file is locked while it is updated, so that entries of concurrent shells are merged.
+    pub(super) fn save(&mut self, config: &Config) -> io::Result<()> {
+        let path = match config.file {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        lock(&file, libc::LOCK_EX)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let mut entries = decode(&content);
+        self.pending = 0;
+        for entry in self.unsaved.drain(..) {
+            if config.erase_dups {
+                entries.retain(|e| *e != entry);
+            }
+            entries.push(entry);
+        }
+        Self::truncate(&mut entries, config.file_size);
+
+        file.seek(io::SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        file.write_all(encode(&entries).as_bytes())
+    }
+
+    /// Remove old entries so that at most `size` entries remain.
+    fn truncate(entries: &mut Vec<String>, size: Option<usize>) {
+        if let Some(size) = size {
+            if entries.len() > size {
+                let excess = entries.len() - size;
+                entries.drain(..excess);
+            }
+        }
+    }
+}
+
+/// Lock `file` with `flock(2)` until it is closed.
+fn lock(file: &fs::File, operation: libc::c_int) -> io::Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn encode(entries: &[String]) -> String {
+    let mut content = String::new();
+    for entry in entries {
+        for c in entry.chars() {
+            match c {
+                '\\' => content.push_str("\\\\"),
+                '\n' => content.push_str("\\n"),
+                c => content.push(c),
+            }
+        }
+        content.push('\n');
+    }
+    content
+}
+
+fn decode(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| {
+            let mut entry = String::new();
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => {
+                        chars.next();
+                        entry.push('\n');
+                    }
+                    ('\\', Some('\\')) => {
+                        chars.next();
+                        entry.push('\\');
+                    }
+                    (c, _) => entry.push(c),
+                }
+            }
+            entry
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            file: None,
+            size: Some(DEFAULT_SIZE),
+            file_size: Some(DEFAULT_SIZE),
+            ignore_space: false,
+            ignore_dups: false,
+            erase_dups: false,
+        }
+    }
+
+    fn history(lines: &[&str], config: &Config) -> Vec<String> {
+        let mut history = History::default();
+        for line in lines {
+            history.add(line, config);
+        }
+        history.entries
+    }
+
+    macro_rules! string_vec {
+        ($($s: expr), *) => { vec![$(String::from($s)), *] }
+    }
+
+    #[test]
+    fn add_test() {
+        let lines = ["a", "b", "b", " c", "a", ""];
+
+        assert_eq!(history(&lines, &config()), string_vec!["a", "b", "b", " c", "a"]);
+        assert_eq!(
+            history(
+                &lines,
+                &Config {
+                    ignore_space: true,
+                    ..config()
+                }
+            ),
+            string_vec!["a", "b", "b", "a"]
+        );
+        assert_eq!(
+            history(
+                &lines,
+                &Config {
+                    ignore_dups: true,
+                    ..config()
+                }
+            ),
+            string_vec!["a", "b", " c", "a"]
+        );
+        assert_eq!(
+            history(
+                &lines,
+                &Config {
+                    erase_dups: true,
+                    ..config()
+                }
+            ),
+            string_vec!["b", " c", "a"]
+        );
+        assert_eq!(
+            history(
+                &lines,
+                &Config {
+                    size: Some(2),
+                    ..config()
+                }
+            ),
+            string_vec![" c", "a"]
+        );
+        assert_eq!(
+            history(
+                &lines,
+                &Config {
+                    size: Some(0),
+                    ..config()
+                }
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn delete_test() {
+        let mut history = History::default();
+        for line in &["a", "b", "c"] {
+            history.add(line, &config());
+        }
+
+        assert!(history.delete(1));
+        assert!(!history.delete(2));
+        assert_eq!(history.entries, string_vec!["a", "c"]);
+        assert_eq!(history.unsaved, string_vec!["a", "c"]);
+
+        let mut history = History::default();
+        for line in &["a", "b", "a", "c"] {
+            history.add(line, &config());
+        }
+        assert!(history.delete(0));
+        assert_eq!(history.unsaved, string_vec!["b", "a", "c"]);
+
+        let config = Config {
+            size: Some(2),
+            erase_dups: true,
+            ..config()
+        };
+        for line in &["d", "b"] {
+            history.add(line, &config);
+        }
+        assert_eq!(history.entries, string_vec!["d", "b"]);
+        assert_eq!(history.unsaved, string_vec!["b", "a", "c", "d", "b"]);
+        assert!(history.delete(0));
+        assert_eq!(history.unsaved, string_vec!["b", "a", "c", "b"]);
+
+        history.clear();
+        assert!(history.entries.is_empty());
+        assert!(history.unsaved.is_empty());
+    }
+
+    #[test]
+    fn parse_size_test() {
+        assert_eq!(Config::parse_size(Some(String::from("10")), Some(5)), Some(10));
+        assert_eq!(Config::parse_size(Some(String::from("-1")), Some(5)), None);
+        assert_eq!(Config::parse_size(Some(String::from("x")), Some(5)), Some(5));
+        assert_eq!(Config::parse_size(None, None), None);
+    }
+
+    #[test]
+    fn encode_test() {
+        let entries = string_vec!["cmd", "echo 'a\nb'", r"a\nb \", ""];
+        let content = encode(&entries);
+
+        assert_eq!(content, "cmd\necho 'a\\nb'\na\\\\nb \\\\\n\n");
+        assert_eq!(decode(&content), entries);
+    }
+}
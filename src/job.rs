@@ -1,10 +1,14 @@
 //! Job and process structs.
+//!
+//! These are also the syntax tree produced by the parser.
 
 use std::fmt;
 use std::process as stdproc;
 
 use errors::{Error, Result};
+use rush::Rush;
 
+/// A pipeline of processes, run in foreground or background.
 #[derive(Debug, PartialEq)]
 pub(super) struct Job {
     process_list: process::Process,
@@ -23,9 +27,8 @@ impl Job {
         Self { process_list, mode }
     }
 
-    pub(super) fn run(&self) -> Result<stdproc::ExitStatus> {
-        let mut child = self.process_list.spawn()?;
-        child.wait()
+    pub(super) fn run(&self, rush: &mut Rush) -> Result<stdproc::ExitStatus> {
+        self.process_list.run(rush)
     }
 }
 
@@ -44,16 +47,29 @@ impl fmt::Display for Job {
 }
 
 pub(super) mod process {
-    use std::fs;
-    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
-    use std::os::unix::process::ExitStatusExt;
+    use std::{fs, io};
+    use std::io::Write;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::path::PathBuf;
+    use std::rc::Rc;
 
-    use builtin;
+    use libc;
+
+    use builtin::{self, command};
+    use completion;
+    use parser;
     use super::*;
 
+    /// A command and its redirections.
+    /// The next process in a pipeline is held in `output`.
     #[derive(Debug, PartialEq)]
     pub(crate) struct Process {
         argument_list: Vec<String>,
+        /// Whether `argument_list` is the words of a conditional expression `[[ ... ]]`
+        /// rather than a command.
+        conditional: bool,
         input: Input,
         output: Output,
     }
@@ -61,7 +77,21 @@ pub(super) mod process {
     #[derive(Debug)]
     enum Child {
         External(stdproc::Child),
-        Builtin,
+        /// Builtin command which has finished with the exit status.
+        Builtin(i32),
+        /// Builtin command running in a forked child, as a process of a pipeline.
+        Forked(libc::pid_t),
+    }
+
+    /// Where a builtin command, or `command_not_found_handle`, runs.
+    #[derive(Clone, Copy)]
+    enum Context<'a> {
+        /// In the shell process, as the whole job.
+        Shell,
+        /// In a forked child, as a process of a pipeline, so that `exit` or `cd` leaves the
+        /// shell as is. The read end of the pipe from the process, if any, is closed in the
+        /// child, for its writes to fail once the next process exits.
+        Pipeline(Option<&'a OwnedFd>),
     }
 
     #[derive(Debug)]
@@ -71,22 +101,28 @@ pub(super) mod process {
     }
 
     impl ChildList {
+        /// Wait for all the processes, returning the exit status of the last one.
         pub(super) fn wait(&mut self) -> Result<stdproc::ExitStatus> {
-            if let Some(ref mut piped) = self.piped {
-                piped.wait()?;
-            }
+            let piped = match self.piped {
+                Some(ref mut piped) => Some(piped.wait()?),
+                None => None,
+            };
 
-            match self.head {
-                Child::External(ref mut child) => child.wait().map_err(Error::from),
-                Child::Builtin => Ok(stdproc::ExitStatus::from_raw(0)),
-            }
+            let head = match self.head {
+                Child::External(ref mut child) => child.wait()?,
+                Child::Builtin(status) => stdproc::ExitStatus::from_raw(status << 8),
+                Child::Forked(pid) => wait_pid(pid)?,
+            };
+            Ok(piped.unwrap_or(head))
         }
     }
 
     #[derive(Debug, PartialEq)]
     pub(crate) enum Input {
         Inherit,
+        /// `< file`
         Redirect(String),
+        /// From the previous process in the pipeline.
         Pipe,
     }
 
@@ -94,12 +130,15 @@ pub(super) mod process {
     pub(crate) enum Output {
         Inherit,
         Redirect(OutputRedirect),
+        /// `| process`
         Pipe(Box<Process>),
     }
 
     #[derive(Debug, PartialEq)]
     pub(crate) enum OutputRedirect {
+        /// `> file`
         Truncate(String),
+        /// `>> file`
         Append(String),
     }
 
@@ -107,33 +146,52 @@ pub(super) mod process {
         pub(crate) fn new(argument_list: Vec<String>, input: Input, output: Output) -> Self {
             Self {
                 argument_list,
+                conditional: false,
                 input,
                 output,
             }
         }
 
-        pub(super) fn spawn(&self) -> Result<ChildList> {
+        pub(crate) fn with_conditional(mut self, conditional: bool) -> Self {
+            self.conditional = conditional;
+            self
+        }
+
+        /// Run the pipeline and wait for it, returning the exit status of the last process.
+        pub(crate) fn run(&self, rush: &mut Rush) -> Result<stdproc::ExitStatus> {
+            self.spawn(rush)?.wait()
+        }
+
+        fn spawn(&self, rush: &mut Rush) -> Result<ChildList> {
             let stdin = match self.input {
-                Input::Inherit => stdproc::Stdio::inherit(),
-                Input::Redirect(ref file_name) => {
-                    let file = fs::File::open(file_name)?;
-                    let file_fd = file.into_raw_fd();
-                    unsafe { stdproc::Stdio::from_raw_fd(file_fd) }
-                }
+                Input::Inherit => None,
+                Input::Redirect(ref file_name) => Some(OwnedFd::from(fs::File::open(file_name)?)),
                 Input::Pipe => unreachable!(),
             };
 
-            self.spawn_rec(stdin)
+            self.spawn_rec(stdin, false, rush)
         }
 
-        fn spawn_rec(&self, stdin: stdproc::Stdio) -> Result<ChildList> {
+        /// Spawn the processes of the pipeline, where `None` for `stdin` inherits the one of the
+        /// shell, and `piped` tells whether it is the output of a previous process.
+        fn spawn_rec(
+            &self,
+            stdin: Option<OwnedFd>,
+            piped: bool,
+            rush: &mut Rush,
+        ) -> Result<ChildList> {
             use self::OutputRedirect::{Append, Truncate};
 
             assert!(!self.argument_list.is_empty());
 
+            let context = if piped {
+                Context::Pipeline(None)
+            } else {
+                Context::Shell
+            };
             let (head, piped) = match self.output {
                 Output::Inherit => {
-                    let head = self.spawn_one(stdin, stdproc::Stdio::inherit())?;
+                    let head = self.spawn_one(stdin, None, context, rush)?;
                     (head, None)
                 }
                 Output::Redirect(ref redir_out) => {
@@ -143,26 +201,17 @@ pub(super) mod process {
                             fs::OpenOptions::new().append(true).open(file_name)
                         }
                     }?;
-                    let file = file.into_raw_fd();
-                    let file = unsafe { stdproc::Stdio::from_raw_fd(file) };
 
-                    let head = self.spawn_one(stdin, file)?;
+                    let head = self.spawn_one(stdin, Some(OwnedFd::from(file)), context, rush)?;
                     (head, None)
                 }
                 Output::Pipe(ref piped) => {
-                    let head = self.spawn_one(stdin, stdproc::Stdio::piped())?;
-
-                    let stdin = match head {
-                        Child::External(ref head) => head.stdout.as_ref().unwrap().as_raw_fd(),
-                        Child::Builtin => {
-                            return Err(Error::Builtin(
-                                String::from("Could not make pipe to builtin commands"),
-                            ));
-                        }
-                    };
-                    let stdin = unsafe { stdproc::Stdio::from_raw_fd(stdin) };
+                    let (reader, writer) = io::pipe()?;
+                    let (reader, writer) = (OwnedFd::from(reader), OwnedFd::from(writer));
+                    let context = Context::Pipeline(Some(&reader));
+                    let head = self.spawn_one(stdin, Some(writer), context, rush)?;
 
-                    let piped = piped.spawn_rec(stdin)?;
+                    let piped = piped.spawn_rec(Some(reader), true, rush)?;
                     (head, Some(Box::new(piped)))
                 }
             };
@@ -170,28 +219,202 @@ pub(super) mod process {
             Ok(ChildList { head, piped })
         }
 
-        fn spawn_one(&self, stdin: stdproc::Stdio, stdout: stdproc::Stdio) -> Result<Child> {
-            match builtin::exec(&self.argument_list) {
-                Some(Ok(())) => Ok(Child::Builtin),
-                Some(Err(e)) => Err(e),
-                None => stdproc::Command::new(&self.argument_list[0])
-                    .args(&self.argument_list[1..])
-                    .stdin(stdin)
-                    .stdout(stdout)
-                    .spawn()
-                    .map(Child::External)
-                    .map_err(Error::from),
+        fn spawn_one(
+            &self,
+            stdin: Option<OwnedFd>,
+            stdout: Option<OwnedFd>,
+            context: Context,
+            rush: &mut Rush,
+        ) -> Result<Child> {
+            let builtins = Rc::clone(&rush.builtins);
+            let builtin = builtins.get(&self.argument_list[0]);
+            if self.conditional || builtin.is_some() {
+                return run_builtin(context, stdin.as_ref(), stdout.as_ref(), rush, |rush| {
+                    // Not through io::stdout, whose buffer would keep the output which fails
+                    // to be written, and print it once the standard output is restored.
+                    let fd = ManuallyDrop::new(unsafe { fs::File::from_raw_fd(1) });
+                    let (mut out, mut err) = (io::BufWriter::new(&*fd), io::stderr());
+                    let location = rush.reader.location();
+                    let mut stdio = builtin::Stdio::new(0, &mut out, &mut err, location);
+                    let status = match builtin {
+                        _ if self.conditional => {
+                            builtin::conditional(rush, &self.argument_list, &mut stdio)
+                        }
+                        Some(builtin) => {
+                            builtin::run(builtin, rush, &self.argument_list, &mut stdio)
+                        }
+                        None => unreachable!(),
+                    };
+                    Ok(status.0)
+                });
+            }
+
+            let name = &self.argument_list[0];
+            let program = if name.contains('/') {
+                PathBuf::from(name)
+            } else {
+                match rush.hash.find(name) {
+                    Some(path) => path.to_owned(),
+                    None => return self.not_found(stdin, stdout, context, rush),
+                }
+            };
+
+            stdproc::Command::new(program)
+                .arg0(name)
+                .args(&self.argument_list[1..])
+                .stdin(stdin.map_or_else(stdproc::Stdio::inherit, stdproc::Stdio::from))
+                .stdout(stdout.map_or_else(stdproc::Stdio::inherit, stdproc::Stdio::from))
+                .spawn()
+                .map(Child::External)
+                .map_err(|e| Error::Exec(name.clone(), e))
+        }
+
+        /// Run `command_not_found_handle` with the argument list if it is defined, as an alias
+        /// or an executable, returning its exit status.
+        /// Otherwise fails with `Error::CommandNotFound`.
+        fn not_found(
+            &self,
+            stdin: Option<OwnedFd>,
+            stdout: Option<OwnedFd>,
+            context: Context,
+            rush: &mut Rush,
+        ) -> Result<Child> {
+            if !handles_not_found(rush) {
+                return Err(Error::CommandNotFound(self.argument_list[0].clone()));
+            }
+
+            let mut line = String::from(HANDLER);
+            for arg in &self.argument_list {
+                line.push(' ');
+                line.push_str(&completion::single_quote(arg));
+            }
+            run_builtin(context, stdin.as_ref(), stdout.as_ref(), rush, |rush| {
+                rush.handling_not_found = true;
+                let status = parser::parse_job(&line, &rush.params).and_then(|job| job.run(rush));
+                rush.handling_not_found = false;
+                status.map(Rush::status_code)
+            })
+        }
+    }
+
+    /// Command run with the argument list of a command not found, if defined.
+    const HANDLER: &str = "command_not_found_handle";
+
+    /// Whether a command not found is passed to `command_not_found_handle`, which is defined and
+    /// not already running.
+    fn handles_not_found(rush: &Rush) -> bool {
+        !rush.handling_not_found && !command::lookup(HANDLER, false, rush).is_empty()
+    }
+
+    /// Run `f`, which runs a builtin command and returns its exit status, with the standard
+    /// input and output replaced by `stdin` and `stdout` if given, in the shell or in a forked
+    /// child as `context` tells.
+    fn run_builtin<F: FnOnce(&mut Rush) -> Result<i32>>(
+        context: Context,
+        stdin: Option<&OwnedFd>,
+        stdout: Option<&OwnedFd>,
+        rush: &mut Rush,
+        f: F,
+    ) -> Result<Child> {
+        let reader = match context {
+            Context::Shell => return with_stdio(stdin, stdout, || f(rush))?.map(Child::Builtin),
+            Context::Pipeline(reader) => reader,
+        };
+
+        io::stdout().flush()?;
+        match unsafe { libc::fork() } {
+            -1 => Err(Error::IO(io::Error::last_os_error())),
+            0 => {
+                if let Some(reader) = reader {
+                    unsafe { libc::close(reader.as_raw_fd()) };
+                }
+                let status = match with_stdio(stdin, stdout, || f(rush)) {
+                    Ok(Ok(status)) => status,
+                    Ok(Err(e)) => {
+                        rush.display_error(&e);
+                        Rush::error_status(&e)
+                    }
+                    Err(e) => {
+                        rush.display_error(&e);
+                        1
+                    }
+                };
+                // Exit without running destructors, which belong to the shell.
+                unsafe { libc::_exit(status) }
+            }
+            pid => Ok(Child::Forked(pid)),
+        }
+    }
+
+    /// Wait for the child process `pid`.
+    fn wait_pid(pid: libc::pid_t) -> io::Result<stdproc::ExitStatus> {
+        let mut status = 0;
+        while unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+        Ok(stdproc::ExitStatus::from_raw(status))
+    }
+
+    /// Run `f`, which runs a builtin command, with the standard input and output of the shell
+    /// replaced by `stdin` and `stdout` if given.
+    fn with_stdio<T, F: FnOnce() -> T>(
+        stdin: Option<&OwnedFd>,
+        stdout: Option<&OwnedFd>,
+        f: F,
+    ) -> io::Result<T> {
+        io::stdout().flush()?;
+        let saved_stdin = stdin.map(|fd| replace_fd(fd, 0)).transpose()?;
+        let saved_stdout = match stdout.map(|fd| replace_fd(fd, 1)).transpose() {
+            Ok(saved) => saved,
+            Err(e) => {
+                if let Some(saved) = saved_stdin {
+                    replace_fd(&saved, 0)?;
+                }
+                return Err(e);
             }
+        };
+
+        let result = f();
+
+        let flushed = io::stdout().flush();
+        if let Some(saved) = saved_stdout {
+            replace_fd(&saved, 1)?;
+        }
+        if let Some(saved) = saved_stdin {
+            replace_fd(&saved, 0)?;
+        }
+        flushed.map(|_| result)
+    }
+
+    /// Make the file descriptor `target` refer to `fd`, returning a duplicate of the previous one.
+    fn replace_fd(fd: &OwnedFd, target: RawFd) -> io::Result<OwnedFd> {
+        let saved = unsafe { libc::fcntl(target, libc::F_DUPFD_CLOEXEC, 10) };
+        if saved < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = unsafe { OwnedFd::from_raw_fd(saved) };
+        if unsafe { libc::dup2(fd.as_raw_fd(), target) } < 0 {
+            return Err(io::Error::last_os_error());
         }
+        Ok(saved)
     }
 
     impl fmt::Display for Process {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             use self::OutputRedirect::{Append, Truncate};
 
+            if self.conditional {
+                write!(f, "[[ ").unwrap();
+            }
             for arg in &self.argument_list {
                 write!(f, "{} ", arg).unwrap();
             }
+            if self.conditional {
+                write!(f, "]] ").unwrap();
+            }
             if let Input::Redirect(ref file_name) = self.input {
                 write!(f, "< {} ", file_name).unwrap()
             }
@@ -206,3 +429,15 @@ pub(super) mod process {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipeline_test() {
+        // Builtins in a pipeline run in child processes, leaving the shell running.
+        let script = "exit 3 | cat\ntrue | exit 5\nexit $?";
+        assert_eq!(Rush::with_command(script, String::from("rush"), vec![]).repl(), 5);
+    }
+}
@@ -1,12 +1,17 @@
-#[macro_use]
-extern crate nom;
+extern crate libc;
 extern crate rustyline;
 
 pub mod rush;
 pub use rush::Rush;
 
 mod reader;
+mod completion;
+mod highlight;
+mod prompt;
 mod parser;
 mod job;
 mod builtin;
 mod errors;
+mod history;
+mod hash;
+mod params;
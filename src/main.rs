@@ -1,9 +1,217 @@
 extern crate rush;
 extern crate rustyline;
 
+use std::{env, process};
+
 use rush::Rush;
 
+const USAGE: &str = "Usage: rush [-il] [--norc] [--noprofile] \
+                     [-c command [name [args...]] | -s [args...] | script [args...]]";
+
+/// Where the commands are read from.
+#[derive(Debug, PartialEq)]
+enum Input {
+    /// `-c command [name [args...]]`
+    Command { command: String, name: String },
+    /// `-s [args...]`, or no arguments
+    Stdin,
+    /// `script [args...]`
+    Script(String),
+}
+
+/// Options given on the command line.
+#[derive(Debug, PartialEq)]
+struct Options {
+    input: Input,
+    /// Positional parameters.
+    args: Vec<String>,
+    login: bool,
+    /// Whether the rc files are run even if the commands are not read from a terminal.
+    interactive: bool,
+    rc: bool,
+    profile: bool,
+}
+
 fn main() {
-    let mut rush = Rush::new();
-    rush.repl();
+    let mut args = env::args();
+    let login = args.next().is_some_and(|arg0| arg0.starts_with('-'));
+
+    let options = match parse_args(args.collect(), login) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+        Err((message, status)) => {
+            eprintln!("rush: {}", message);
+            process::exit(status);
+        }
+    };
+
+    Rush::init_pwd();
+    match start(options) {
+        Ok(mut rush) => process::exit(rush.repl()),
+        Err((message, status)) => {
+            eprintln!("rush: {}", message);
+            process::exit(status);
+        }
+    }
+}
+
+/// Parse the command line arguments `args`, where `login` tells whether the shell is started as
+/// a login shell. Returns `None` if the usage is asked by `--help`.
+/// On failure, returns an error message and the exit status.
+fn parse_args(args: Vec<String>, login: bool) -> Result<Option<Options>, (String, i32)> {
+    let mut command = false;
+    let mut stdin = false;
+    let mut options = Options {
+        input: Input::Stdin,
+        args: vec![],
+        login,
+        interactive: false,
+        rc: true,
+        profile: true,
+    };
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.peek().cloned() {
+        match arg.as_ref() {
+            "--login" => options.login = true,
+            "--norc" => options.rc = false,
+            "--noprofile" => options.profile = false,
+            "--" => {
+                args.next();
+                break;
+            }
+            "-h" | "--help" => return Ok(None),
+            _ if arg.starts_with('-') && !arg.starts_with("--") && arg.len() > 1 => {
+                // Single-letter flags may be clustered, as in `-lc`.
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'c' => command = true,
+                        's' => stdin = true,
+                        'l' => options.login = true,
+                        'i' => options.interactive = true,
+                        _ => return Err((format!("-{}: invalid option\n{}", flag, USAGE), 2)),
+                    }
+                }
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err((format!("{}: invalid option\n{}", arg, USAGE), 2));
+            }
+            _ => break,
+        }
+        args.next();
+    }
+    let mut args: Vec<String> = args.collect();
+
+    options.input = if command {
+        if args.is_empty() {
+            return Err((format!("-c: option requires an argument\n{}", USAGE), 2));
+        }
+        let command = args.remove(0);
+        let name = if args.is_empty() {
+            String::from("rush")
+        } else {
+            args.remove(0)
+        };
+        Input::Command { command, name }
+    } else if stdin || args.is_empty() {
+        Input::Stdin
+    } else {
+        Input::Script(args.remove(0))
+    };
+    options.args = args;
+
+    Ok(Some(options))
+}
+
+/// Create a shell as specified by `options`, and run its startup files.
+/// On failure, returns an error message and the exit status.
+fn start(options: Options) -> Result<Rush, (String, i32)> {
+    let mut rush = match options.input {
+        Input::Command { command, name } => Rush::with_command(&command, name, options.args),
+        Input::Stdin => Rush::with_stdin(options.args),
+        Input::Script(script) => Rush::with_script(&script, options.args)
+            .map_err(|e| (format!("{}: {}", script, e), 127))?,
+    };
+
+    let interactive = options.interactive || rush.is_interactive();
+    rush.run_startup_files(options.login && options.profile, interactive && options.rc);
+
+    Ok(rush)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, (String, i32)> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect(), false)
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let options = parse(&["-c", "echo $0 $1", "name", "a"]).unwrap().unwrap();
+        assert_eq!(
+            options.input,
+            Input::Command {
+                command: String::from("echo $0 $1"),
+                name: String::from("name"),
+            }
+        );
+        assert_eq!(options.args, strings(&["a"]));
+        assert!(!options.login && !options.interactive && options.rc && options.profile);
+
+        let options = parse(&["-c", "echo"]).unwrap().unwrap();
+        assert_eq!(
+            options.input,
+            Input::Command {
+                command: String::from("echo"),
+                name: String::from("rush"),
+            }
+        );
+        assert_eq!(parse(&["-c"]).unwrap_err().1, 2);
+
+        let options = parse(&["-s", "a", "b"]).unwrap().unwrap();
+        assert_eq!(options.input, Input::Stdin);
+        assert_eq!(options.args, strings(&["a", "b"]));
+        assert_eq!(parse(&[]).unwrap().unwrap().input, Input::Stdin);
+
+        let options = parse(&["--norc", "script.sh", "-c", "a"]).unwrap().unwrap();
+        assert_eq!(options.input, Input::Script(String::from("script.sh")));
+        assert_eq!(options.args, strings(&["-c", "a"]));
+        assert!(!options.rc);
+
+        let options = parse(&["--", "-script.sh"]).unwrap().unwrap();
+        assert_eq!(options.input, Input::Script(String::from("-script.sh")));
+
+        assert_eq!(parse(&["--help"]).unwrap(), None);
+        assert_eq!(parse(&["-x"]).unwrap_err(), (format!("-x: invalid option\n{}", USAGE), 2));
+        assert_eq!(
+            parse(&["--noprofil"]).unwrap_err(),
+            (format!("--noprofil: invalid option\n{}", USAGE), 2)
+        );
+    }
+
+    #[test]
+    fn clustered_flags_test() {
+        let options = parse(&["-lc", "echo"]).unwrap().unwrap();
+        assert!(options.login);
+        assert_eq!(
+            options.input,
+            Input::Command {
+                command: String::from("echo"),
+                name: String::from("rush"),
+            }
+        );
+
+        let options = parse(&["-ic", "echo"]).unwrap().unwrap();
+        assert!(options.interactive && !options.login);
+        assert_eq!(parse(&["-lx"]).unwrap_err().0, format!("-x: invalid option\n{}", USAGE));
+    }
 }
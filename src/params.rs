@@ -0,0 +1,154 @@
+//! Shell parameters.
+
+use std::{env, ffi, process};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use libc;
+
+/// Values of aliases by name.
+pub(super) type Aliases = HashMap<String, String>;
+
+/// Parameters referred by `$name`, besides environment variables.
+#[derive(Debug, Default)]
+pub(super) struct Parameters {
+    /// `$0`
+    pub(super) name: String,
+    /// `$1`, `$2`, ...
+    pub(super) positional: Vec<String>,
+    /// `$?`
+    pub(super) status: i32,
+    /// Directory stack of `pushd`, from the top, below the current directory.
+    /// `~1`, `~2`, ... refer to it.
+    pub(super) dir_stack: Vec<String>,
+    /// Array variables, whose elements are referred by `${name[n]}`, all elements by
+    /// `${name[@]}`, and the first one by `$name`.
+    pub(super) arrays: HashMap<String, Vec<String>>,
+    /// Aliases defined by `alias`, expanded before parsing a job.
+    /// They are shared with the line editor, which completes and highlights them.
+    pub(super) aliases: Rc<RefCell<Aliases>>,
+}
+
+impl Parameters {
+    /// Value of the special parameter or environment variable `name`.
+    pub(super) fn get(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.get_element(name) {
+            return value;
+        }
+
+        match name {
+            "0" => Some(self.name.clone()),
+            "#" => Some(self.positional.len().to_string()),
+            "?" => Some(self.status.to_string()),
+            "$" => Some(process::id().to_string()),
+            "@" | "*" => Some(self.positional.join(" ")),
+            _ => match name.parse::<usize>() {
+                Ok(n) => n.checked_sub(1).and_then(|i| self.positional.get(i)).cloned(),
+                Err(_) => env::var(name).ok(),
+            },
+        }
+    }
+
+    /// Value of `name` or `name[subscript]` if `name` is an array.
+    fn get_element(&self, name: &str) -> Option<Option<String>> {
+        let (name, subscript) = match name.find('[') {
+            Some(i) if name.ends_with(']') => (&name[..i], &name[i + 1..name.len() - 1]),
+            Some(_) => return None,
+            None => (name, "0"),
+        };
+        let array = self.arrays.get(name)?;
+
+        Some(match subscript {
+            "@" | "*" => Some(array.join(" ")),
+            n => n.parse::<usize>().ok().and_then(|n| array.get(n).cloned()),
+        })
+    }
+
+    /// Value of the tilde prefix `~prefix`:
+    /// the home directory of the user `prefix`, or of the current user if empty,
+    /// `$PWD` for `+`, `$OLDPWD` for `-`,
+    /// and the `N`-th entry of the directory stack listed by `dirs` for `N`, `+N` and `-N`.
+    pub(super) fn tilde(&self, prefix: &str) -> Option<String> {
+        match prefix {
+            "+" => return env::var("PWD").ok(),
+            "-" => return env::var("OLDPWD").ok(),
+            _ => {}
+        }
+
+        let (from_end, digits) = match prefix.as_bytes().first() {
+            Some(b'+') => (false, &prefix[1..]),
+            Some(b'-') => (true, &prefix[1..]),
+            _ => (false, prefix),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return home_dir(prefix);
+        }
+
+        let n = digits.parse::<usize>().ok()?;
+        let len = self.dir_stack.len() + 1;
+        let index = if from_end { len.checked_sub(n + 1)? } else { n };
+        match index {
+            0 => env::var("PWD").ok(),
+            index => self.dir_stack.get(index - 1).cloned(),
+        }
+    }
+}
+
+/// Home directory of `user`, or `$HOME` if empty.
+pub(super) fn home_dir(user: &str) -> Option<String> {
+    if user.is_empty() {
+        return env::var("HOME")
+            .ok()
+            .or_else(|| env::home_dir().map(|home| home.display().to_string()));
+    }
+
+    let user = ffi::CString::new(user).ok()?;
+    unsafe {
+        let entry = libc::getpwnam(user.as_ptr());
+        if entry.is_null() {
+            None
+        } else {
+            let dir = ffi::CStr::from_ptr((*entry).pw_dir);
+            Some(dir.to_string_lossy().into_owned())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tilde_test() {
+        let params = Parameters {
+            dir_stack: vec![String::from("/a"), String::from("/b")],
+            ..Parameters::default()
+        };
+
+        assert_eq!(params.tilde("1"), Some(String::from("/a")));
+        assert_eq!(params.tilde("+2"), Some(String::from("/b")));
+        assert_eq!(params.tilde("-0"), Some(String::from("/b")));
+        assert_eq!(params.tilde("-1"), Some(String::from("/a")));
+        assert_eq!(params.tilde("3"), None);
+        assert_eq!(params.tilde("-3"), None);
+        let root = unsafe { libc::getpwnam(b"root\0".as_ptr() as *const _) };
+        let root = unsafe { ffi::CStr::from_ptr((*root).pw_dir) }.to_string_lossy();
+        assert_eq!(params.tilde("root").as_deref(), Some(&*root));
+        assert_eq!(params.tilde("no-such-user-rush"), None);
+    }
+
+    #[test]
+    fn get_element_test() {
+        let mut params = Parameters::default();
+        params
+            .arrays
+            .insert(String::from("A"), vec![String::from("x"), String::from("y")]);
+
+        assert_eq!(params.get("A"), Some(String::from("x")));
+        assert_eq!(params.get("A[1]"), Some(String::from("y")));
+        assert_eq!(params.get("A[2]"), None);
+        assert_eq!(params.get("A[@]"), Some(String::from("x y")));
+        assert_eq!(params.get("B[0]"), None);
+    }
+}
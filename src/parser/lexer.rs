@@ -0,0 +1,647 @@
+//! Lexer splitting a line into tokens.
+//!
+//! Words are separated by spaces and tabs, and by the operators `<`, `>`, `>>`, `|`, `&`, `;`
+//! and newline.
+//! A `#` at the start of a word begins a comment, which continues to the end of the line.
+//! Within a word,
+//!
+//! * `\` escapes the next character, and `\` followed by a newline is removed,
+//! * `'...'` quotes characters literally,
+//! * `"..."` quotes characters, except that `\` escapes `"`, `\` and newline,
+//! * `$name`, `${name}` and the special parameters `$0`...`$9`, `$#`, `$?`, `$$`, `$@` and `$*`
+//!   are expanded, except within single quotes.
+//!
+//! The result of an expansion is not split into words, except for `$@` and `$*` which expand to
+//! one word per positional parameter. Within double quotes, `$*` is a single word joining them by
+//! the first character of `$IFS`.
+//!
+//! A conditional expression `[[ ... ]]` starting a command is lexed differently. `&&`, `||`,
+//! `(`, `)`, `<` and `>` are words rather than operators, except that `(`, `)` and `|` are
+//! part of the regular expression after `=~`. When expanding, quoted characters other than
+//! alphanumerics are escaped by `\` so that they match literally in patterns.
+
+use std::mem;
+
+use errors::{Error, Result};
+use params::Parameters;
+
+/// Byte range `start..end` of a token in the input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) struct Span {
+    pub(super) start: usize,
+    pub(super) end: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct Token {
+    pub(super) kind: TokenKind,
+    pub(super) span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum TokenKind {
+    /// A word with quotes and escapes removed.
+    Word(String),
+    /// `<`
+    Less,
+    /// `>`
+    Great,
+    /// `>>`
+    DoubleGreat,
+    /// `|`
+    Pipe,
+    /// `&`
+    Ampersand,
+    /// `;`
+    Semicolon,
+    /// `\n` or `\r`
+    Newline,
+    /// `[[` starting a conditional expression
+    DoubleLeftBracket,
+    /// `]]` ending a conditional expression
+    DoubleRightBracket,
+}
+
+/// Split `input` into tokens, expanding parameters with `params` if given.
+///
+/// Returns `Error::Incomplete` if `input` ends inside quotes or with a `\`.
+pub(super) fn tokenize(input: &str, params: Option<&Parameters>) -> Result<Vec<Token>> {
+    let mut lexer = Lexer {
+        input,
+        params,
+        pos: 0,
+        tokens: vec![],
+        conditional: false,
+        regex: false,
+    };
+    lexer.run()?;
+    Ok(lexer.tokens)
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    params: Option<&'a Parameters>,
+    pos: usize,
+    tokens: Vec<Token>,
+    /// Whether inside `[[ ... ]]`.
+    conditional: bool,
+    /// Whether the next word is a regular expression following `=~`.
+    regex: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn run(&mut self) -> Result<()> {
+        while let Some(c) = self.peek() {
+            let start = self.pos;
+            self.regex = self.conditional
+                && self
+                    .tokens
+                    .last()
+                    .is_some_and(|t| t.kind == TokenKind::Word(String::from("=~")));
+            let kind = match c {
+                ' ' | '\t' => {
+                    self.bump();
+                    continue;
+                }
+                '\\' if self.input[self.pos..].starts_with("\\\n") => {
+                    self.pos += 2;
+                    continue;
+                }
+                '#' => {
+                    self.comment();
+                    continue;
+                }
+                '[' if !self.conditional && self.command_start() && self.keyword("[[") => {
+                    self.conditional = true;
+                    self.pos += 2;
+                    TokenKind::DoubleLeftBracket
+                }
+                ']' if self.conditional && self.keyword("]]") => {
+                    self.conditional = false;
+                    self.pos += 2;
+                    TokenKind::DoubleRightBracket
+                }
+                '(' | ')' | '|' if self.regex => {
+                    self.words(start)?;
+                    continue;
+                }
+                '&' | '|' if self.conditional && self.input[self.pos + 1..].starts_with(c) => {
+                    self.pos += 2;
+                    TokenKind::Word(self.input[start..self.pos].to_owned())
+                }
+                '<' | '>' | '(' | ')' if self.conditional => {
+                    self.bump();
+                    TokenKind::Word(c.to_string())
+                }
+                '<' => self.operator(TokenKind::Less),
+                '>' if self.input[self.pos..].starts_with(">>") => {
+                    self.bump();
+                    self.operator(TokenKind::DoubleGreat)
+                }
+                '>' => self.operator(TokenKind::Great),
+                '|' => self.operator(TokenKind::Pipe),
+                '&' => self.operator(TokenKind::Ampersand),
+                ';' => self.operator(TokenKind::Semicolon),
+                '\n' | '\r' => self.operator(TokenKind::Newline),
+                _ => {
+                    self.words(start)?;
+                    continue;
+                }
+            };
+
+            self.push(kind, start);
+        }
+
+        Ok(())
+    }
+
+    fn words(&mut self, start: usize) -> Result<()> {
+        for word in self.word()? {
+            self.push(TokenKind::Word(word), start);
+        }
+        Ok(())
+    }
+
+    /// Whether the next token starts a command.
+    fn command_start(&self) -> bool {
+        match self.tokens.last() {
+            None => true,
+            Some(token) => matches!(
+                token.kind,
+                TokenKind::Pipe | TokenKind::Ampersand | TokenKind::Semicolon | TokenKind::Newline
+            ),
+        }
+    }
+
+    /// Whether `keyword` follows as a whole word.
+    fn keyword(&self, keyword: &str) -> bool {
+        let rest = &self.input[self.pos..];
+        rest.starts_with(keyword)
+            && rest[keyword.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| " \t\n\r;&|<>".contains(c))
+    }
+
+    fn push(&mut self, kind: TokenKind, start: usize) {
+        self.tokens.push(Token {
+            kind,
+            span: Span {
+                start,
+                end: self.pos,
+            },
+        });
+    }
+
+    fn operator(&mut self, kind: TokenKind) -> TokenKind {
+        self.bump();
+        kind
+    }
+
+    /// Skip to the end of the line, leaving the newline.
+    fn comment(&mut self) {
+        self.pos = self.input[self.pos..]
+            .find(['\n', '\r'])
+            .map_or(self.input.len(), |len| self.pos + len);
+    }
+
+    /// Read a word, which may be expanded to multiple words by `$@` or `$*`.
+    fn word(&mut self) -> Result<Vec<String>> {
+        let mut words = vec![];
+        let mut word = String::new();
+        self.tilde(&mut word);
+        // Whether the word consists only of expansions to no words, so that it is removed.
+        let mut removed = word.is_empty();
+
+        while let Some(c) = self.peek() {
+            match c {
+                '(' | ')' | '|' if self.regex => {
+                    self.bump();
+                    word.push(c);
+                    removed = false;
+                }
+                ' ' | '\t' | '<' | '>' | '|' | '&' | ';' | '\n' | '\r' => break,
+                '(' | ')' if self.conditional => break,
+                '\\' => {
+                    self.bump();
+                    removed = false;
+                    match self.bump() {
+                        Some('\n') => {}
+                        Some(c) => self.push_quoted(&mut word, c),
+                        None => return Err(Error::Incomplete),
+                    }
+                }
+                '\'' => {
+                    self.bump();
+                    removed = false;
+                    loop {
+                        match self.bump() {
+                            Some('\'') => break,
+                            Some(c) => self.push_quoted(&mut word, c),
+                            None => return Err(Error::Incomplete),
+                        }
+                    }
+                }
+                '"' => {
+                    self.bump();
+                    if self.peek() == Some('"') {
+                        removed = false;
+                    }
+                    loop {
+                        if !matches!(self.peek(), Some('"') | Some('$')) {
+                            removed = false;
+                        }
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('$') => {
+                                if !self.parameter(&mut words, &mut word, true)? {
+                                    removed = false;
+                                }
+                            }
+                            Some('\\') => match self.bump() {
+                                Some('\n') => {}
+                                Some(c @ '"') | Some(c @ '\\') => self.push_quoted(&mut word, c),
+                                Some(c) => {
+                                    self.push_quoted(&mut word, '\\');
+                                    self.push_quoted(&mut word, c);
+                                }
+                                None => return Err(Error::Incomplete),
+                            },
+                            Some(c) => self.push_quoted(&mut word, c),
+                            None => return Err(Error::Incomplete),
+                        }
+                    }
+                }
+                '$' => {
+                    self.bump();
+                    if !self.parameter(&mut words, &mut word, false)? {
+                        removed = false;
+                    }
+                }
+                c => {
+                    self.bump();
+                    word.push(c);
+                    removed = false;
+                }
+            }
+        }
+
+        if !removed {
+            words.push(word);
+        }
+
+        Ok(words)
+    }
+
+    /// Expand an unquoted tilde prefix `~prefix` at the start of a word onto `word`.
+    /// It is left as it is if the prefix has no value.
+    fn tilde(&mut self, word: &mut String) {
+        let params = match self.params {
+            Some(params) if self.peek() == Some('~') => params,
+            _ => return,
+        };
+
+        let rest = &self.input[self.pos + 1..];
+        let len = rest
+            .find(|c| " \t<>|&;\n\r/".contains(c))
+            .unwrap_or(rest.len());
+        let prefix = &rest[..len];
+        if prefix.contains(['\\', '\'', '"', '$']) {
+            return;
+        }
+        if let Some(value) = params.tilde(prefix) {
+            word.push_str(&value);
+            self.pos += 1 + len;
+        }
+    }
+
+    /// Push the quoted character `c` onto `word`, escaping it inside `[[ ... ]]`.
+    fn push_quoted(&self, word: &mut String, c: char) {
+        if self.conditional && self.params.is_some() && !c.is_alphanumeric() {
+            word.push('\\');
+        }
+        word.push(c);
+    }
+
+    /// Push the result of an expansion onto `word`.
+    fn push_expanded(&self, word: &mut String, value: &str, quoted: bool) {
+        if quoted {
+            for c in value.chars() {
+                self.push_quoted(word, c);
+            }
+        } else {
+            word.push_str(value);
+        }
+    }
+
+    /// Expand a parameter following `$` onto `word`, which is within double quotes if `quoted`.
+    /// For `$@` and unquoted `$*`, each positional parameter but the last completes a word into
+    /// `words`, while quoted `$*` joins them by the first character of `$IFS`.
+    ///
+    /// Returns whether the parameter expands to no words, as `$@` does without positional
+    /// parameters.
+    fn parameter(
+        &mut self,
+        words: &mut Vec<String>,
+        word: &mut String,
+        quoted: bool,
+    ) -> Result<bool> {
+        let start = self.pos;
+        let name = match self.peek() {
+            Some('{') => {
+                self.bump();
+                let len = self.input[self.pos..].find('}').ok_or(Error::Incomplete)?;
+                let name = &self.input[self.pos..self.pos + len];
+                self.pos += len + 1;
+                name
+            }
+            Some(c) if c.is_ascii_digit() || "#?$@*".contains(c) => {
+                let name = &self.input[self.pos..self.pos + 1];
+                self.bump();
+                name
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = self.input[self.pos..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(self.input.len() - self.pos);
+                let name = &self.input[self.pos..self.pos + len];
+                self.pos += len;
+                name
+            }
+            _ => {
+                self.push_expanded(word, "$", quoted);
+                return Ok(false);
+            }
+        };
+
+        let params = match self.params {
+            Some(params) => params,
+            None => {
+                word.push('$');
+                word.push_str(&self.input[start..self.pos]);
+                return Ok(false);
+            }
+        };
+
+        if name == "*" && quoted {
+            let separator = params.get("IFS").map_or(Some(' '), |ifs| ifs.chars().next());
+            let mut joined = String::new();
+            for (i, param) in params.positional.iter().enumerate() {
+                if i > 0 {
+                    joined.extend(separator);
+                }
+                joined.push_str(param);
+            }
+            self.push_expanded(word, &joined, quoted);
+        } else if name == "@" || name == "*" {
+            if params.positional.is_empty() {
+                return Ok(true);
+            }
+            for (i, param) in params.positional.iter().enumerate() {
+                if i > 0 {
+                    words.push(mem::take(word));
+                }
+                self.push_expanded(word, param, quoted);
+            }
+        } else if let Some(value) = params.get(name) {
+            self.push_expanded(word, &value, quoted);
+        }
+
+        Ok(false)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::TokenKind::*;
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        kinds_with(input, &Parameters::default())
+    }
+
+    fn kinds_with(input: &str, params: &Parameters) -> Vec<TokenKind> {
+        tokenize(input, Some(params))
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect()
+    }
+
+    macro_rules! string_vec {
+        ($($s: expr), *) => { vec![$(String::from($s)), *] }
+    }
+
+    fn word(s: &str) -> TokenKind {
+        Word(String::from(s))
+    }
+
+    #[test]
+    fn word_test() {
+        assert_eq!(kinds("t"), vec![word("t")]);
+        assert_eq!(kinds("token"), vec![word("token")]);
+        assert_eq!(kinds("token<"), vec![word("token"), Less]);
+        assert_eq!(
+            kinds("token>|&"),
+            vec![word("token"), Great, Pipe, Ampersand]
+        );
+        assert_eq!(kinds("token "), vec![word("token")]);
+        assert_eq!(kinds("token token"), vec![word("token"), word("token")]);
+        assert_eq!(
+            kinds("token\ttoken  "),
+            vec![word("token"), word("token")]
+        );
+
+        assert_eq!(kinds(""), vec![]);
+    }
+
+    #[test]
+    fn operator_test() {
+        assert_eq!(
+            kinds("< > >> | & ; \n \r"),
+            vec![
+                Less,
+                Great,
+                DoubleGreat,
+                Pipe,
+                Ampersand,
+                Semicolon,
+                Newline,
+                Newline,
+            ]
+        );
+        assert_eq!(kinds(">>>"), vec![DoubleGreat, Great]);
+    }
+
+    #[test]
+    fn quote_test() {
+        assert_eq!(kinds(r"a\ b"), vec![word("a b")]);
+        assert_eq!(kinds(r"\|\\"), vec![word(r"|\")]);
+        assert_eq!(kinds("a\\\nb"), vec![word("ab")]);
+        assert_eq!(kinds("a \\\n b"), vec![word("a"), word("b")]);
+
+        assert_eq!(kinds(r"'a | b'"), vec![word("a | b")]);
+        assert_eq!(kinds(r"'a\'"), vec![word(r"a\")]);
+        assert_eq!(kinds(r#"'"'"#), vec![word("\"")]);
+
+        assert_eq!(kinds(r#""a | b""#), vec![word("a | b")]);
+        assert_eq!(kinds(r#""\"\\\a""#), vec![word(r#""\\a"#)]);
+        assert_eq!(kinds(r#""'""#), vec![word("'")]);
+
+        assert_eq!(kinds(r#"a'b'"c"d"#), vec![word("abcd")]);
+        assert_eq!(kinds("''"), vec![word("")]);
+    }
+
+    #[test]
+    fn comment_test() {
+        assert_eq!(kinds("# comment"), vec![]);
+        assert_eq!(kinds("cmd # comment | x"), vec![word("cmd")]);
+        assert_eq!(kinds("cmd #comment\ncmd"), vec![word("cmd"), Newline, word("cmd")]);
+        assert_eq!(kinds("cmd;#comment"), vec![word("cmd"), Semicolon]);
+        assert_eq!(kinds("cmd # 'unterminated"), vec![word("cmd")]);
+
+        assert_eq!(kinds("a#b"), vec![word("a#b")]);
+        assert_eq!(kinds("a# b"), vec![word("a#"), word("b")]);
+        assert_eq!(kinds("'#' \"#\" \\#"), vec![word("#"), word("#"), word("#")]);
+    }
+
+    #[test]
+    fn conditional_test() {
+        assert_eq!(
+            kinds("[[ a&&(b||c<d) ]] | x [[ y ]]"),
+            vec![
+                DoubleLeftBracket,
+                word("a"),
+                word("&&"),
+                word("("),
+                word("b"),
+                word("||"),
+                word("c"),
+                word("<"),
+                word("d"),
+                word(")"),
+                DoubleRightBracket,
+                Pipe,
+                word("x"),
+                word("[["),
+                word("y"),
+                word("]]"),
+            ]
+        );
+        assert_eq!(
+            kinds(r#"[[ 'a*' == "$0.?"\* ]]"#),
+            vec![
+                DoubleLeftBracket,
+                word("a\\*"),
+                word("=="),
+                word("\\.\\?\\*"),
+                DoubleRightBracket,
+            ]
+        );
+        assert_eq!(kinds("[[x ]]"), vec![word("[[x"), word("]]")]);
+        assert_eq!(
+            kinds("[[ a =~ (b|c)' '(d) ]]"),
+            vec![
+                DoubleLeftBracket,
+                word("a"),
+                word("=~"),
+                word("(b|c)\\ (d)"),
+                DoubleRightBracket,
+            ]
+        );
+    }
+
+    #[test]
+    fn tilde_test() {
+        let params = Parameters {
+            dir_stack: string_vec!["/a"],
+            ..Parameters::default()
+        };
+        let kinds = |input| kinds_with(input, &params);
+
+        assert_eq!(kinds("~1/b x~1 ~1a"), vec![word("/a/b"), word("x~1"), word("~1a")]);
+        assert_eq!(kinds("'~1' \\~1 ~'1' ~2"), vec![word("~1"), word("~1"), word("~1"), word("~2")]);
+        let root = ::params::home_dir("root").unwrap();
+        assert_eq!(kinds("~root/x"), vec![word(&format!("{}/x", root))]);
+    }
+
+    #[test]
+    fn parameter_test() {
+        let params = Parameters {
+            name: String::from("script"),
+            positional: string_vec!["a b", "c"],
+            status: 1,
+            ..Parameters::default()
+        };
+        let kinds = |input| kinds_with(input, &params);
+
+        assert_eq!(kinds("$0 $1 $2 $3"), vec![word("script"), word("a b"), word("c"), word("")]);
+        assert_eq!(kinds("$# $?"), vec![word("2"), word("1")]);
+        assert_eq!(kinds("x$1y ${2}z"), vec![word("xa by"), word("cz")]);
+        assert_eq!(kinds(r#""$1" '$1' \$1"#), vec![word("a b"), word("$1"), word("$1")]);
+        assert_eq!(kinds("$ a$ $-"), vec![word("$"), word("a$"), word("$-")]);
+
+        assert_eq!(kinds("$@"), vec![word("a b"), word("c")]);
+        assert_eq!(kinds(r#"x"$@"y"#), vec![word("xa b"), word("cy")]);
+        assert_eq!(kinds("$*"), vec![word("a b"), word("c")]);
+        assert_eq!(kinds(r#""$*" x"$*"y"#), vec![word("a b c"), word("xa b cy")]);
+
+        let empty = Parameters::default();
+        assert_eq!(kinds_with("$@ $*", &empty), vec![]);
+        assert_eq!(kinds_with(r#""$@""#, &empty), vec![]);
+        assert_eq!(kinds_with("x$@", &empty), vec![word("x")]);
+        assert_eq!(kinds_with(r#""$*" ""$@"#, &empty), vec![word(""), word("")]);
+
+        let raw = |input| -> Vec<_> {
+            tokenize(input, None)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.kind)
+                .collect()
+        };
+        assert_eq!(raw("$1 ${2}x \"$@\""), vec![word("$1"), word("${2}x"), word("$@")]);
+    }
+
+    #[test]
+    fn span_test() {
+        let spans: Vec<_> = tokenize(" ab >> 'c d'", None)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.span.start, t.span.end))
+            .collect();
+        assert_eq!(spans, vec![(1, 3), (4, 6), (7, 12)]);
+    }
+
+    #[test]
+    fn incomplete_test() {
+        macro_rules! assert_incomplete {
+            ($s: expr) => {
+                match tokenize($s, None) {
+                    Err(Error::Incomplete) => {}
+                    r => panic!("{:?}", r),
+                }
+            }
+        }
+
+        assert_incomplete!("cmd arg \\");
+        assert_incomplete!("\\");
+        assert_incomplete!("cmd \\\\\\");
+        assert_incomplete!("'a");
+        assert_incomplete!("a \"b");
+        assert_incomplete!("\"a\\\"");
+
+        assert_incomplete!("${name");
+
+        assert!(tokenize("cmd \\\\", None).is_ok());
+    }
+}
@@ -0,0 +1,973 @@
+//! Job parser.
+//!
+//! A line is split into tokens by the `lexer`, which are then parsed by a recursive-descent
+//! parser into a `Job`, the syntax tree of rush.
+//!
+//! # Syntax of job
+//! Tokens are `word`s and the operators below.
+//!
+//! ```ignore
+//! arg_list     := word+
+//!
+//! redir_in     := "<" word
+//! redir_trunc  := ">" word
+//! redir_append := ">>" word
+//! redir_out    := redir_trunc
+//!               | redir_append
+//!
+//! cond_expr    := "[[" (word | newline)* "]]"
+//! command      := arg_list
+//!               | cond_expr
+//!
+//! proc_cdr     := command proc_out?
+//! pipe_proc    := "|" newline* proc_cdr
+//! proc_out     := pipe_proc
+//!               | redir_out
+//!
+//! proc_car     := command redir_in? proc_out?
+//!
+//! end_job      := (";" | newline)* eof
+//! job          := proc_car "&"? end_job
+//! ```
+//!
+//! Input ending after `|`, inside quotes or with `\` is *incomplete*, i.e. a prefix of a valid
+//! job that needs more input, rather than invalid.
+
+mod lexer;
+
+use std::fmt;
+use std::ops::Range;
+
+use job::{Job, JobMode};
+use job::process::{self, Process};
+use errors::{Error, Result};
+use params::{Aliases, Parameters};
+use self::lexer::{Token, TokenKind};
+
+/// Syntax error in a job.
+#[derive(Debug, PartialEq)]
+pub(super) struct ParseError {
+    /// The whole input.
+    pub(super) input: String,
+    /// Byte offset of the unexpected token in `input`.
+    pub(super) offset: usize,
+    /// Description of what was expected at `offset`.
+    pub(super) expected: &'static str,
+    /// The unexpected token, or `newline` at the end of input.
+    pub(super) found: String,
+}
+
+impl ParseError {
+    /// The line of `input` containing the unexpected token.
+    pub(super) fn line(&self) -> &str {
+        let start = self.line_start();
+        let end = self.input[start..]
+            .find('\n')
+            .map_or(self.input.len(), |len| start + len);
+        &self.input[start..end]
+    }
+
+    /// A line with `^` under the unexpected token, to be printed below `line()`.
+    pub(super) fn marker(&self) -> String {
+        let column = self.input[self.line_start()..self.offset].chars().count();
+        format!("{}^", " ".repeat(column))
+    }
+
+    fn line_start(&self) -> usize {
+        self.input[..self.offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "syntax error near unexpected token `{}' (expected {})",
+            self.found, self.expected
+        )
+    }
+}
+
+/// Parse `input` to a job, expanding parameters with `params`.
+///
+/// Returns `Error::Incomplete` if `input` is unfinished and should be continued on the next line.
+pub(super) fn parse_job(input: &str, params: &Parameters) -> Result<Job> {
+    let input = expand_aliases(input, &params.aliases.borrow());
+    Parser::new(&input, params)?.job()
+}
+
+/// Replace unquoted command names of `input` which are aliases with their values.
+///
+/// The value of an alias is expanded again, except for the alias itself. If the value ends with
+/// a blank, the next word is also checked for an alias.
+fn expand_aliases(input: &str, aliases: &Aliases) -> String {
+    if aliases.is_empty() {
+        return input.to_owned();
+    }
+    let mut expanding = vec![];
+    expand_aliases_rec(input, aliases, &mut expanding).0
+}
+
+/// Expand aliases other than the ones being `expanding`.
+/// Returns the result and whether it ends with an alias whose value ends with a blank.
+fn expand_aliases_rec(
+    input: &str,
+    aliases: &Aliases,
+    expanding: &mut Vec<String>,
+) -> (String, bool) {
+    let tokens = match lexer::tokenize(input, None) {
+        Ok(tokens) => tokens,
+        Err(_) => return (input.to_owned(), false),
+    };
+
+    let mut expanded = String::new();
+    let mut end = 0;
+    let mut command_expected = true;
+    let mut redirect = false;
+    let mut check_next = false;
+    for token in tokens {
+        expanded.push_str(&input[end..token.span.start]);
+        let raw = &input[token.span.start..token.span.end];
+        end = token.span.end;
+
+        let alias = match token.kind {
+            TokenKind::Word(ref word)
+                if (command_expected || check_next) && !redirect && word == raw =>
+            {
+                aliases.get(word).filter(|_| !expanding.contains(word))
+            }
+            _ => None,
+        };
+        match (alias, token.kind) {
+            (Some(value), TokenKind::Word(word)) => {
+                expanding.push(word);
+                let (value, trailing) = expand_aliases_rec(value, aliases, expanding);
+                expanding.pop();
+                expanded.push_str(&value);
+                check_next = trailing || value.ends_with([' ', '\t']);
+                command_expected = false;
+                continue;
+            }
+            (_, TokenKind::Word(_)) | (_, TokenKind::DoubleLeftBracket) => {
+                if !redirect {
+                    command_expected = false;
+                }
+                redirect = false;
+            }
+            (_, TokenKind::Less) | (_, TokenKind::Great) | (_, TokenKind::DoubleGreat) => {
+                redirect = true
+            }
+            (_, TokenKind::DoubleRightBracket) => {}
+            _ => command_expected = true,
+        }
+        expanded.push_str(raw);
+        check_next = false;
+    }
+    expanded.push_str(&input[end..]);
+
+    (expanded, check_next && end == input.len())
+}
+
+/// Whether `input` has no job, i.e. consists of blanks and comments only.
+pub(super) fn is_empty(input: &str, params: &Parameters) -> bool {
+    lexer::tokenize(input, Some(params))
+        .map(|tokens| tokens.iter().all(|t| t.kind == TokenKind::Newline))
+        .unwrap_or(false)
+}
+
+/// Split `input` into words and operators as written, with quotes and without expansion.
+/// Falls back to splitting at whitespace if `input` is incomplete.
+pub(super) fn split_words(input: &str) -> Vec<&str> {
+    match lexer::tokenize(input, None) {
+        Ok(tokens) => tokens
+            .iter()
+            .map(|t| &input[t.span.start..t.span.end])
+            .collect(),
+        Err(_) => input.split_whitespace().collect(),
+    }
+}
+
+/// The word being typed at the end of the input, for completion.
+#[derive(Debug, PartialEq)]
+pub(super) struct PartialWord {
+    /// Byte offset of the word in the input.
+    pub(super) start: usize,
+    /// The word with quotes and escapes removed, and without expansion.
+    pub(super) value: String,
+    /// The quote opening the word, if any.
+    pub(super) quote: Option<char>,
+    pub(super) position: WordPosition,
+    /// The command name of the process, if the word is not the command name itself.
+    pub(super) command: Option<String>,
+    /// The word before this word in the process, or empty if none.
+    pub(super) previous: String,
+}
+
+/// Where a word appears in a job.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(super) enum WordPosition {
+    /// The command name of a process.
+    Command,
+    /// An argument of a command.
+    Argument,
+    /// The file name of a redirection.
+    Redirect,
+}
+
+/// Find the word at the end of `input`, which may be empty or inside unclosed quotes.
+pub(super) fn partial_word(input: &str) -> PartialWord {
+    let mut tokens = tokenize_partial(input);
+
+    let word = match tokens.last() {
+        Some(&Token {
+            kind: TokenKind::Word(ref value),
+            span,
+        }) if span.end >= input.len() => Some((span.start, value.clone())),
+        _ => None,
+    };
+    if word.is_some() {
+        tokens.pop();
+    }
+    let (start, value) = word.unwrap_or_else(|| (input.len(), String::new()));
+
+    let position = match tokens.last().map(|t| &t.kind) {
+        Some(&TokenKind::Word(_)) => WordPosition::Argument,
+        Some(&TokenKind::Less) | Some(&TokenKind::Great) | Some(&TokenKind::DoubleGreat) => {
+            WordPosition::Redirect
+        }
+        _ => WordPosition::Command,
+    };
+
+    // The process of the word starts after the last `|`, `&`, `;` or newline.
+    let process_start = tokens
+        .iter()
+        .rposition(|t| {
+            matches!(
+                t.kind,
+                TokenKind::Pipe | TokenKind::Ampersand | TokenKind::Semicolon | TokenKind::Newline
+            )
+        })
+        .map_or(0, |i| i + 1);
+    let mut command = None;
+    let mut redirect = false;
+    for token in &tokens[process_start..] {
+        match token.kind {
+            TokenKind::Word(ref word) if !redirect => {
+                command = Some(word.clone());
+                break;
+            }
+            TokenKind::Word(_) => redirect = false,
+            _ => redirect = true,
+        }
+    }
+
+    let previous = match tokens[process_start..].last() {
+        Some(&Token {
+            kind: TokenKind::Word(ref word),
+            ..
+        }) => word.clone(),
+        _ => String::new(),
+    };
+
+    PartialWord {
+        start,
+        value,
+        quote: input[start..].chars().next().filter(|&c| c == '\'' || c == '"'),
+        position,
+        command,
+        previous,
+    }
+}
+
+/// A part of the input to be highlighted.
+#[derive(Debug, PartialEq)]
+pub(super) enum Highlight {
+    /// A command name, with quotes and escapes removed.
+    Command(String),
+    /// Other words.
+    Word,
+    /// An operator, including redirections.
+    Operator,
+}
+
+/// Split `input`, which may be incomplete, into words and operators for syntax highlighting.
+pub(super) fn highlights(input: &str) -> Vec<(Range<usize>, Highlight)> {
+    let mut command_expected = true;
+    let mut redirect = false;
+
+    tokenize_partial(input)
+        .into_iter()
+        .map(|token| {
+            let highlight = match token.kind {
+                TokenKind::Word(word) => {
+                    let highlight = if command_expected && !redirect {
+                        command_expected = false;
+                        Highlight::Command(word)
+                    } else {
+                        Highlight::Word
+                    };
+                    redirect = false;
+                    highlight
+                }
+                TokenKind::Less | TokenKind::Great | TokenKind::DoubleGreat => {
+                    redirect = true;
+                    Highlight::Operator
+                }
+                TokenKind::DoubleLeftBracket | TokenKind::DoubleRightBracket => {
+                    command_expected = false;
+                    Highlight::Operator
+                }
+                _ => {
+                    command_expected = true;
+                    redirect = false;
+                    Highlight::Operator
+                }
+            };
+            (token.span.start..token.span.end.min(input.len()), highlight)
+        })
+        .collect()
+}
+
+/// Split `input` into tokens without expansion, closing quotes left open at the end.
+fn tokenize_partial(input: &str) -> Vec<Token> {
+    ["", "'", "\""]
+        .iter()
+        .filter_map(|quote| lexer::tokenize(&format!("{}{}", input, quote), None).ok())
+        .next()
+        .unwrap_or_default()
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, params: &Parameters) -> Result<Self> {
+        Ok(Self {
+            input,
+            tokens: lexer::tokenize(input, Some(params))?,
+            pos: 0,
+        })
+    }
+
+    fn job(&mut self) -> Result<Job> {
+        let process_list = self.process_car()?;
+        let mode = if self.eat(&TokenKind::Ampersand) {
+            JobMode::BackGround
+        } else {
+            JobMode::ForeGround
+        };
+        self.end_of_job()?;
+
+        Ok(Job::new(process_list, mode))
+    }
+
+    fn end_of_job(&mut self) -> Result<()> {
+        let mut expected = "end of job";
+        while self.eat(&TokenKind::Semicolon) || self.eat(&TokenKind::Newline) {
+            expected = "end of line";
+        }
+
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error(expected)),
+        }
+    }
+
+    fn process_car(&mut self) -> Result<Process> {
+        let (argument_list, conditional) = self.command()?;
+        let input = match self.peek() {
+            Some(&TokenKind::Less) => self.redirect_in()?,
+            _ => process::Input::Inherit,
+        };
+        let output = self.process_output()?;
+
+        Ok(Process::new(argument_list, input, output).with_conditional(conditional))
+    }
+
+    fn process_cdr(&mut self) -> Result<Process> {
+        let (argument_list, conditional) = self.command()?;
+        let output = self.process_output()?;
+
+        Ok(Process::new(argument_list, process::Input::Pipe, output).with_conditional(conditional))
+    }
+
+    /// Parse a command, returning its arguments, or the words of a conditional expression and
+    /// true.
+    fn command(&mut self) -> Result<(Vec<String>, bool)> {
+        if !self.eat(&TokenKind::DoubleLeftBracket) {
+            return Ok((self.argument_list()?, false));
+        }
+
+        let mut words = vec![];
+        loop {
+            match self.peek() {
+                Some(&TokenKind::Word(_)) => words.push(self.word("word")?),
+                Some(&TokenKind::Newline) => self.pos += 1,
+                Some(&TokenKind::DoubleRightBracket) => break,
+                Some(_) => return Err(self.error("`]]'")),
+                None => return Err(Error::Incomplete),
+            }
+        }
+        if words.is_empty() {
+            return Err(self.error("conditional expression"));
+        }
+        self.pos += 1;
+
+        Ok((words, true))
+    }
+
+    fn process_output(&mut self) -> Result<process::Output> {
+        match self.peek() {
+            Some(&TokenKind::Pipe) => self.pipe_process(),
+            Some(&TokenKind::Great) | Some(&TokenKind::DoubleGreat) => self.redirect_out(),
+            _ => Ok(process::Output::Inherit),
+        }
+    }
+
+    fn pipe_process(&mut self) -> Result<process::Output> {
+        self.expect(&TokenKind::Pipe, "`|'")?;
+        while self.eat(&TokenKind::Newline) {}
+        if self.peek().is_none() {
+            return Err(Error::Incomplete);
+        }
+
+        let process = self.process_cdr()?;
+        Ok(process::Output::Pipe(Box::new(process)))
+    }
+
+    fn argument_list(&mut self) -> Result<Vec<String>> {
+        let mut argument_list = vec![self.word("command")?];
+        while let Some(&TokenKind::Word(_)) = self.peek() {
+            argument_list.push(self.word("command")?);
+        }
+
+        Ok(argument_list)
+    }
+
+    fn redirect_in(&mut self) -> Result<process::Input> {
+        self.expect(&TokenKind::Less, "`<'")?;
+        let file_name = self.word("file name")?;
+
+        Ok(process::Input::Redirect(file_name))
+    }
+
+    fn redirect_out(&mut self) -> Result<process::Output> {
+        use job::process::OutputRedirect::{Append, Truncate};
+
+        let redirect = if self.eat(&TokenKind::Great) {
+            Truncate
+        } else {
+            self.expect(&TokenKind::DoubleGreat, "`>' or `>>'")?;
+            Append
+        };
+        let file_name = self.word("file name")?;
+
+        Ok(process::Output::Redirect(redirect(file_name)))
+    }
+
+    fn word(&mut self, expected: &'static str) -> Result<String> {
+        match self.tokens.get(self.pos) {
+            Some(&Token {
+                kind: TokenKind::Word(ref word),
+                ..
+            }) => {
+                let word = word.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    fn expect(&mut self, kind: &TokenKind, expected: &'static str) -> Result<()> {
+        if self.eat(kind) {
+            Ok(())
+        } else {
+            Err(self.error(expected))
+        }
+    }
+
+    /// Consume the next token if it is `kind`.
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        if self.peek() == Some(kind) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    /// Syntax error at the next token.
+    fn error(&self, expected: &'static str) -> Error {
+        let (offset, found) = match self.tokens.get(self.pos) {
+            Some(&Token {
+                kind: TokenKind::Newline,
+                span,
+            }) => (span.start, "newline"),
+            Some(&Token { span, .. }) => (span.start, &self.input[span.start..span.end]),
+            None => (self.input.len(), "newline"),
+        };
+
+        Error::Parse(ParseError {
+            input: self.input.to_owned(),
+            offset,
+            expected,
+            found: found.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! string_vec {
+        ($($s: expr), *) => { vec![$(String::from($s)), *] }
+    }
+
+    /// Parse `input` with `f`, returning the rest of `input` not consumed, and the result.
+    fn parse<'a, T, F>(f: F, input: &'a str) -> Option<(&'a str, T)>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T>,
+    {
+        let mut parser = Parser::new(input, &Parameters::default()).ok()?;
+        let result = f(&mut parser).ok()?;
+        let rest = parser
+            .tokens
+            .get(parser.pos)
+            .map_or("", |t| &input[t.span.start..]);
+        Some((rest, result))
+    }
+
+    fn done<T>(rest: &str, result: T) -> Option<(&str, T)> {
+        Some((rest, result))
+    }
+
+    fn parse_job(input: &str) -> Result<Job> {
+        super::parse_job(input, &Parameters::default())
+    }
+
+    fn is_empty(input: &str) -> bool {
+        super::is_empty(input, &Parameters::default())
+    }
+
+    #[test]
+    fn argument_list_test() {
+        assert_eq!(parse(Parser::argument_list, "cmd"), done("", string_vec!["cmd"]));
+        assert_eq!(
+            parse(Parser::argument_list, "cmd arg"),
+            done("", string_vec!["cmd", "arg"])
+        );
+        assert_eq!(
+            parse(Parser::argument_list, " cmd  arg0\targ1 \t"),
+            done("", string_vec!["cmd", "arg0", "arg1"])
+        );
+    }
+
+    #[test]
+    fn redirect_in_test() {
+        use self::process::Input::Redirect;
+
+        assert_eq!(
+            parse(Parser::redirect_in, "< file_name"),
+            done("", Redirect(String::from("file_name")))
+        );
+        assert_eq!(
+            parse(Parser::redirect_in, " <file_name "),
+            done("", Redirect(String::from("file_name")))
+        );
+    }
+
+    #[test]
+    fn redirect_out_test() {
+        use self::process::Output::Redirect;
+        use self::process::OutputRedirect::{Append, Truncate};
+
+        assert_eq!(
+            parse(Parser::redirect_out, "> file_name"),
+            done("", Redirect(Truncate(String::from("file_name"))))
+        );
+        assert_eq!(
+            parse(Parser::redirect_out, " >file_name "),
+            done("", Redirect(Truncate(String::from("file_name"))))
+        );
+        assert_eq!(
+            parse(Parser::redirect_out, ">> file_name"),
+            done("", Redirect(Append(String::from("file_name"))))
+        );
+    }
+
+    #[test]
+    fn process_test() {
+        use self::process::{Input, Output};
+        use self::process::OutputRedirect::{Append, Truncate};
+
+        assert_eq!(
+            parse(Parser::process_car, "cmd"),
+            done("",
+                Process::new(string_vec!["cmd"], Input::Inherit, Output::Inherit,),
+            )
+        );
+        assert_eq!(
+            parse(Parser::process_car, "cmd < file"),
+            done("",
+                Process::new(
+                    string_vec!["cmd"],
+                    Input::Redirect(String::from("file")),
+                    Output::Inherit,
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::process_car, "cmd > file"),
+            done("",
+                Process::new(
+                    string_vec!["cmd"],
+                    Input::Inherit,
+                    Output::Redirect(Truncate(String::from("file")))
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::process_car, "cmd arg0 arg1 < file0 >> file1"),
+            done("",
+                Process::new(
+                    string_vec!["cmd", "arg0", "arg1"],
+                    Input::Redirect(String::from("file0")),
+                    Output::Redirect(Append(String::from("file1")))
+                ),
+            )
+        );
+
+        assert_eq!(
+            parse(Parser::process_car, "cmd arg0 arg1 < file0 >> file1").map(|(_, p)| p),
+            parse(Parser::process_car, " cmd \t arg0 arg1 < file0 >> file1\n").map(|(_, p)| p)
+        );
+
+        assert!(parse(Parser::process_car, "< file cmd").is_none());
+        assert!(parse(Parser::process_car, "> file cmd").is_none());
+        assert!(
+            if let Some((remained, _)) = parse(Parser::process_car, "cmd > file0 < file1") {
+                remained == "< file1"
+            } else {
+                false
+            }
+        );
+    }
+
+    #[test]
+    fn job_test() {
+        use self::process::{Input, Output};
+        use self::process::OutputRedirect::{Append, Truncate};
+
+        assert_eq!(
+            parse(Parser::job, "cmd"),
+            done("",
+                Job::new(
+                    Process::new(string_vec!["cmd"], Input::Inherit, Output::Inherit,),
+                    JobMode::ForeGround,
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::job, "cmd < file0 > file1"),
+            done("",
+                Job::new(
+                    Process::new(
+                        string_vec!["cmd"],
+                        Input::Redirect(String::from("file0")),
+                        Output::Redirect(Truncate(String::from("file1"))),
+                    ),
+                    JobMode::ForeGround,
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::job, "cmd0 | cmd1"),
+            done("",
+                Job::new(
+                    {
+                        let proc1 = Process::new(string_vec!["cmd1"], Input::Pipe, Output::Inherit);
+                        Process::new(
+                            string_vec!["cmd0"],
+                            Input::Inherit,
+                            Output::Pipe(Box::new(proc1)),
+                        )
+                    },
+                    JobMode::ForeGround,
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::job, "cmd0 < file0 | cmd1 arg1 > file1"),
+            done("",
+                Job::new(
+                    {
+                        let proc1 = Process::new(
+                            string_vec!["cmd1", "arg1"],
+                            Input::Pipe,
+                            Output::Redirect(Truncate(String::from("file1"))),
+                        );
+                        Process::new(
+                            string_vec!["cmd0"],
+                            Input::Redirect(String::from("file0")),
+                            Output::Pipe(Box::new(proc1)),
+                        )
+                    },
+                    JobMode::ForeGround,
+                ),
+            )
+        );
+        assert_eq!(
+            parse(Parser::job, "cmd0 < file0 | cmd1 arg1 | cmd2 arg2 arg3 >> file3 &"),
+            done("",
+                Job::new(
+                    {
+                        let proc2 = Process::new(
+                            string_vec!["cmd2", "arg2", "arg3"],
+                            Input::Pipe,
+                            Output::Redirect(Append(String::from("file3"))),
+                        );
+                        let proc1 = Process::new(
+                            string_vec!["cmd1", "arg1"],
+                            Input::Pipe,
+                            Output::Pipe(Box::new(proc2)),
+                        );
+                        Process::new(
+                            string_vec!["cmd0"],
+                            Input::Redirect(String::from("file0")),
+                            Output::Pipe(Box::new(proc1)),
+                        )
+                    },
+                    JobMode::BackGround,
+                ),
+            )
+        );
+
+        assert_eq!(
+            parse(Parser::job, "[[ a && \n 'b c' ]] | cmd"),
+            done("",
+                Job::new(
+                    Process::new(
+                        string_vec!["a", "&&", "b\\ c"],
+                        Input::Inherit,
+                        Output::Pipe(Box::new(
+                            Process::new(string_vec!["cmd"], Input::Pipe, Output::Inherit)
+                        )),
+                    ).with_conditional(true),
+                    JobMode::ForeGround,
+                ),
+            )
+        );
+
+        assert_eq!(
+            parse(Parser::job, "cmd0 < file0 | cmd1 arg1 | cmd2 arg2 arg3 >> file3 &"),
+            parse(Parser::job, " cmd0 < file0 \t | cmd1 arg1 | cmd2 arg2 arg3 >> file3 & \n")
+        );
+
+        macro_rules! assert_err {
+            ($s: expr) => { assert!(parse_job($s).is_err()) }
+        }
+
+        assert_err!("| cmd");
+        assert_err!("cmd |");
+        assert_err!("&");
+        assert_err!("cmd < file |");
+
+        assert_err!("> file");
+        assert_err!("file >");
+
+        assert_err!("& cmd");
+        assert_err!("cmd0 & | cmd1");
+
+        assert_err!("cmd0 > file | cmd1");
+        assert_err!("cmd0 | cmd1 < file");
+        assert_err!("cmd0 | cmd1 > file | cmd2");
+
+        assert_err!("[[ ]]");
+        assert_err!("[[ a ; b ]]");
+    }
+
+    #[test]
+    fn incomplete_test() {
+        fn is_incomplete(input: &str) -> bool {
+            matches!(parse_job(input), Err(Error::Incomplete))
+        }
+
+        macro_rules! assert_incomplete {
+            ($s: expr) => { assert!(is_incomplete($s)) }
+        }
+        macro_rules! assert_not_incomplete {
+            ($s: expr) => { assert!(!is_incomplete($s)) }
+        }
+
+        assert_incomplete!("cmd |");
+        assert_incomplete!("cmd0 | cmd1 | \n");
+        assert_incomplete!("cmd < file |");
+        assert_incomplete!("[[ a &&");
+
+        assert_not_incomplete!("");
+        assert_not_incomplete!("cmd");
+        assert_not_incomplete!("| cmd");
+        assert_not_incomplete!("|");
+        assert_not_incomplete!("cmd > file |");
+    }
+
+    #[test]
+    fn expand_aliases_test() {
+        let mut aliases = Aliases::new();
+        for &(name, value) in &[
+            ("ll", "ls -l"),
+            ("ls", "ls --color"),
+            ("sudo", "sudo "),
+            ("loop", "loop2"),
+            ("loop2", "loop"),
+        ] {
+            aliases.insert(String::from(name), String::from(value));
+        }
+
+        assert_eq!(
+            expand_aliases("ll a | ll >ll", &aliases),
+            "ls --color -l a | ls --color -l >ll"
+        );
+        assert_eq!(expand_aliases("'ll' \\ll ll", &aliases), "'ll' \\ll ll");
+        assert_eq!(expand_aliases("< ll ll", &aliases), "< ll ls --color -l");
+        assert_eq!(expand_aliases("sudo ll ll", &aliases), "sudo  ls --color -l ll");
+        assert_eq!(expand_aliases("loop", &aliases), "loop");
+        assert_eq!(expand_aliases("[[ ll ]]; ll", &aliases), "[[ ll ]]; ls --color -l");
+        assert_eq!(expand_aliases("ll 'a", &aliases), "ll 'a");
+    }
+
+    #[test]
+    fn is_empty_test() {
+        assert!(is_empty(""));
+        assert!(is_empty(" \t"));
+        assert!(is_empty("# comment"));
+        assert!(is_empty("  # comment\n"));
+
+        assert!(!is_empty("cmd # comment"));
+        assert!(!is_empty("'# comment'"));
+        assert!(!is_empty("'unterminated"));
+    }
+
+    #[test]
+    fn split_words_test() {
+        assert_eq!(
+            split_words("cmd 'a b' $x|cmd2>>f"),
+            vec!["cmd", "'a b'", "$x", "|", "cmd2", ">>", "f"]
+        );
+        assert_eq!(split_words("cmd 'a b"), vec!["cmd", "'a", "b"]);
+    }
+
+    #[test]
+    fn partial_word_test() {
+        use self::WordPosition::*;
+
+        let word =
+            |start, value: &str, quote, position| (start, String::from(value), quote, position);
+        let partial_word = |input| {
+            let word = partial_word(input);
+            (word.start, word.value, word.quote, word.position)
+        };
+
+        assert_eq!(partial_word(""), word(0, "", None, Command));
+        assert_eq!(partial_word("ca"), word(0, "ca", None, Command));
+        assert_eq!(partial_word("cat "), word(4, "", None, Argument));
+        assert_eq!(partial_word("cat a\\ b"), word(4, "a b", None, Argument));
+        assert_eq!(partial_word("cat 'a b"), word(4, "a b", Some('\''), Argument));
+        assert_eq!(partial_word("cat \"$HO"), word(4, "$HO", Some('"'), Argument));
+        assert_eq!(partial_word("cat <f"), word(5, "f", None, Redirect));
+        assert_eq!(partial_word("cat a|"), word(6, "", None, Command));
+        assert_eq!(partial_word("cat a; l"), word(7, "l", None, Command));
+
+        let command = |input| {
+            let word = super::partial_word(input);
+            (word.command, word.previous)
+        };
+        assert_eq!(command("cat"), (None, String::new()));
+        assert_eq!(command("cat "), (Some(String::from("cat")), String::from("cat")));
+        assert_eq!(command("a | <f cat b c"), (Some(String::from("cat")), String::from("b")));
+        assert_eq!(command("a <f"), (Some(String::from("a")), String::new()));
+    }
+
+    #[test]
+    fn highlights_test() {
+        use self::Highlight::*;
+
+        assert_eq!(
+            highlights("cat <f 'a b' | \\wc -l >"),
+            vec![
+                (0..3, Command(String::from("cat"))),
+                (4..5, Operator),
+                (5..6, Word),
+                (7..12, Word),
+                (13..14, Operator),
+                (15..18, Command(String::from("wc"))),
+                (19..21, Word),
+                (22..23, Operator),
+            ]
+        );
+        assert_eq!(
+            highlights("<f cmd \"a"),
+            vec![
+                (0..1, Operator),
+                (1..2, Word),
+                (3..6, Command(String::from("cmd"))),
+                (7..9, Word),
+            ]
+        );
+        assert_eq!(
+            highlights("[[ -f x ]]"),
+            vec![(0..2, Operator), (3..5, Word), (6..7, Word), (8..10, Operator)]
+        );
+    }
+
+    #[test]
+    fn parse_error_test() {
+        macro_rules! assert_parse_error {
+            ($s: expr, $offset: expr, $expected: expr, $found: expr) => {
+                match parse_job($s) {
+                    Err(Error::Parse(e)) => {
+                        assert_eq!((e.offset, e.expected, e.found.as_ref()), ($offset, $expected, $found))
+                    }
+                    r => panic!("{:?}", r),
+                }
+            }
+        }
+
+        assert_parse_error!("| cmd", 0, "command", "|");
+        assert_parse_error!("& cmd", 0, "command", "&");
+        assert_parse_error!("cmd >", 5, "file name", "newline");
+        assert_parse_error!("cmd > < file", 6, "file name", "<");
+        assert_parse_error!("cmd0 | | cmd1", 7, "command", "|");
+        assert_parse_error!("cmd0 & | cmd1", 7, "end of job", "|");
+        assert_parse_error!("cmd0 > file | cmd1", 12, "end of job", "|");
+        assert_parse_error!("cmd0 | cmd1 < file", 12, "end of job", "<");
+        assert_parse_error!("cmd0 ; cmd1", 7, "end of line", "cmd1");
+
+        let e = match parse_job("cmd0 > file | cmd1") {
+            Err(Error::Parse(e)) => e,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(e.line(), "cmd0 > file | cmd1");
+        assert_eq!(e.marker(), "            ^");
+        assert_eq!(
+            e.to_string(),
+            "syntax error near unexpected token `|' (expected end of job)"
+        );
+
+        let e = match parse_job("cmd0 |\ncmd1 > file |") {
+            Err(Error::Parse(e)) => e,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(e.line(), "cmd1 > file |");
+        assert_eq!(e.marker(), "            ^");
+    }
+}
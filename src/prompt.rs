@@ -0,0 +1,337 @@
+//! Prompts given by `PS1` and `PS2`.
+//!
+//! A prompt is decoded from the backslash escapes below, then `$name`, `${name}` and
+//! `$(command)` are expanded, except in what the escapes produce: `\$HOME` is `$HOME`.
+//!
+//! * `\u`: user name,
+//! * `\h`, `\H`: host name up to the first `.`, and the whole host name,
+//! * `\w`, `\W`: current directory, and its basename, with `$HOME` abbreviated to `~`,
+//! * `\$`: `#` for the superuser, `$` otherwise,
+//! * `\t`, `\A`, `\d`: time as `HH:MM:SS` and `HH:MM`, and date as `Tue May 26`,
+//! * `\j`: number of jobs, which is always 0 as jobs are not managed yet,
+//! * `\!`: history number of the next entry,
+//! * `\?`: exit status of the last job,
+//! * `\s`, `\v`: shell name and version,
+//! * `\n`, `\e`, `\a`, `\\`, `\nnn`: newline, escape, bell, backslash and octal character code,
+//! * `\[`, `\]`: start and end of non-printing characters, such as color sequences or a
+//!   terminal title, which are left out when the line editor computes the width of the prompt.
+//!
+//! `PS4` is not supported, as commands are not traced: there is no `set -x` to enable it.
+
+use std::{env, ffi, process};
+
+use libc;
+
+use builtin;
+use params::Parameters;
+
+/// Markers of the start and the end of non-printing characters in an expanded prompt, as in
+/// readline.
+const START_IGNORE: char = '\x01';
+const END_IGNORE: char = '\x02';
+/// `$` produced by a backslash escape, such as `\$` or `\w`, which is not expanded.
+const LITERAL_DOLLAR: char = '\x03';
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Expand the prompt string `ps`, with `history_number` for `\!`.
+/// Non-printing characters are enclosed in markers, removed by `display` and `plain`.
+pub(super) fn expand(ps: &str, params: &Parameters, history_number: usize) -> String {
+    expand_parameters(&decode(ps, params, history_number), params)
+        .replace(LITERAL_DOLLAR, "$")
+}
+
+/// The expanded `prompt` as shown on the terminal.
+pub(super) fn display(prompt: &str) -> String {
+    prompt.replace([START_IGNORE, END_IGNORE], "")
+}
+
+/// The expanded `prompt` without its non-printing characters, which has the width of the
+/// displayed prompt.
+pub(super) fn plain(prompt: &str) -> String {
+    let mut plain = String::new();
+    let mut ignored = false;
+    for c in prompt.chars() {
+        match c {
+            START_IGNORE => ignored = true,
+            END_IGNORE => ignored = false,
+            c if !ignored => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Decode the backslash escapes.
+/// A `$` they produce, as `\$` does, is replaced by `LITERAL_DOLLAR` not to be expanded.
+fn decode(ps: &str, params: &Parameters, history_number: usize) -> String {
+    let mut prompt = String::new();
+    let mut chars = ps.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+
+        let decoded = match chars.next() {
+            Some('u') => user_name(),
+            Some('h') => host_name().split('.').next().unwrap_or("").to_owned(),
+            Some('H') => host_name(),
+            Some('w') => current_dir(false),
+            Some('W') => current_dir(true),
+            Some('$') => String::from(if unsafe { libc::geteuid() } == 0 { "#" } else { "$" }),
+            Some('t') => {
+                let tm = local_time();
+                format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+            }
+            Some('A') => {
+                let tm = local_time();
+                format!("{:02}:{:02}", tm.tm_hour, tm.tm_min)
+            }
+            Some('d') => {
+                let tm = local_time();
+                format!(
+                    "{} {} {:02}",
+                    DAYS[tm.tm_wday as usize % 7],
+                    MONTHS[tm.tm_mon as usize % 12],
+                    tm.tm_mday
+                )
+            }
+            Some('j') => String::from("0"),
+            Some('!') => history_number.to_string(),
+            Some('?') => params.status.to_string(),
+            Some('s') => String::from("rush"),
+            Some('v') => String::from(env!("CARGO_PKG_VERSION")),
+            Some('n') => String::from("\n"),
+            Some('e') => String::from("\x1b"),
+            Some('a') => String::from("\x07"),
+            Some('\\') => String::from("\\"),
+            Some('[') => START_IGNORE.to_string(),
+            Some(']') => END_IGNORE.to_string(),
+            Some(c @ '0'..='7') => {
+                let mut code = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                ::std::char::from_u32(code).map_or_else(String::new, String::from)
+            }
+            Some(c) => format!("\\{}", c),
+            None => String::from("\\"),
+        };
+        prompt.extend(decoded.chars().map(|c| if c == '$' { LITERAL_DOLLAR } else { c }));
+    }
+
+    prompt
+}
+
+/// Expand `$name`, `${name}` and `$(command)`.
+/// Unlike in commands, `$` not followed by a name is left as it is.
+fn expand_parameters(prompt: &str, params: &Parameters) -> String {
+    let mut expanded = String::new();
+    let mut rest = prompt;
+
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if rest.starts_with('(') {
+            match matching_paren(rest) {
+                Some(end) => {
+                    expanded.push_str(&command_output(&rest[1..end]));
+                    rest = &rest[end + 1..];
+                }
+                None => expanded.push('$'),
+            }
+        } else if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => {
+                    expanded.push_str(&params.get(&rest[1..end]).unwrap_or_default());
+                    rest = &rest[end + 1..];
+                }
+                None => expanded.push('$'),
+            }
+        } else {
+            let len = match rest.chars().next() {
+                Some(c) if c.is_ascii_digit() || "#?$@*".contains(c) => 1,
+                _ => rest
+                    .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                    .unwrap_or(rest.len()),
+            };
+            if len == 0 {
+                expanded.push('$');
+            } else {
+                expanded.push_str(&params.get(&rest[..len]).unwrap_or_default());
+                rest = &rest[len..];
+            }
+        }
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Position of the `)` closing the `(` at the start of `s`.
+fn matching_paren(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Output of `command` run by `rush -c`, without trailing newlines.
+fn command_output(command: &str) -> String {
+    let output = env::current_exe().and_then(|rush| {
+        process::Command::new(rush)
+            .args(["-c", command])
+            .stdin(process::Stdio::null())
+            .output()
+    });
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches('\n')
+            .to_owned(),
+        Err(_) => String::new(),
+    }
+}
+
+fn user_name() -> String {
+    unsafe {
+        let entry = libc::getpwuid(libc::geteuid());
+        if entry.is_null() {
+            env::var("USER").unwrap_or_default()
+        } else {
+            ffi::CStr::from_ptr((*entry).pw_name)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+fn host_name() -> String {
+    let mut name = [0u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr() as *mut _, name.len()) } != 0 {
+        return String::new();
+    }
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+/// Current directory with `$HOME` abbreviated to `~`, or only its basename if `base`.
+/// It is the logical one kept in `$PWD`, the same as `pwd` prints.
+fn current_dir(base: bool) -> String {
+    let dir = builtin::logical_dir().display().to_string();
+
+    let home = env::var("HOME").unwrap_or_default();
+    if !home.is_empty() && (dir == home || dir.starts_with(&format!("{}/", home))) {
+        if base && dir == home {
+            return String::from("~");
+        } else if !base {
+            return format!("~{}", &dir[home.len()..]);
+        }
+    }
+
+    if base && dir != "/" {
+        dir.rsplit('/').next().unwrap_or("").to_owned()
+    } else {
+        dir
+    }
+}
+
+fn local_time() -> libc::tm {
+    unsafe {
+        let time = libc::time(::std::ptr::null_mut());
+        let mut tm = ::std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_test() {
+        let params = Parameters {
+            status: 127,
+            ..Parameters::default()
+        };
+        let decode = |ps| decode(ps, &params, 42);
+
+        assert_eq!(decode("rush $ "), "rush $ ");
+        assert_eq!(decode("\\! \\? \\j\\n\\\\"), "42 127 0\n\\");
+        assert_eq!(
+            decode("\\[\\e[1;32m\\]>\\[\\033[0m\\] "),
+            "\x01\x1b[1;32m\x02>\x01\x1b[0m\x02 "
+        );
+        assert_eq!(decode("\\101\\x\\"), "A\\x\\");
+        assert_eq!(
+            decode("\\$"),
+            if unsafe { libc::geteuid() } == 0 { "#" } else { "\x03" }
+        );
+        assert_eq!(decode("\\t").len(), 8);
+    }
+
+    #[test]
+    fn expand_test() {
+        let params = Parameters::default();
+        let dollar = if unsafe { libc::geteuid() } == 0 { "#" } else { "$" };
+
+        assert_eq!(expand("\\$HOME \\044x", &params, 1), format!("{}HOME $x", dollar));
+        assert_eq!(expand("$? \\$?", &params, 1), format!("0 {}?", dollar));
+    }
+
+    #[test]
+    fn plain_test() {
+        let prompt = "\x01\x1b]0;title\x07\x02\x01\x1b[32m\x02rush $\x01\x1b[0m\x02 ";
+
+        assert_eq!(display(prompt), "\x1b]0;title\x07\x1b[32mrush $\x1b[0m ");
+        assert_eq!(plain(prompt), "rush $ ");
+        assert_eq!(plain("> "), "> ");
+    }
+
+    #[test]
+    fn expand_parameters_test() {
+        let params = Parameters {
+            positional: vec![String::from("a")],
+            status: 1,
+            ..Parameters::default()
+        };
+        // The environment is shared by the tests running in parallel, so the variable is
+        // unique to this test and removed at the end.
+        env::set_var("RUSH_PROMPT_EXPAND_TEST", "x");
+
+        assert_eq!(
+            expand_parameters(
+                "$RUSH_PROMPT_EXPAND_TEST-${RUSH_PROMPT_EXPAND_TEST}y $1 $? $",
+                &params
+            ),
+            "x-xy a 1 $"
+        );
+        assert_eq!(expand_parameters("$UNSET_RUSH_PROMPT_TEST.", &params), ".");
+        assert_eq!(expand_parameters("$(unclosed", &params), "$(unclosed");
+        assert_eq!(matching_paren("(a (b) c) d"), Some(8));
+
+        env::remove_var("RUSH_PROMPT_EXPAND_TEST");
+    }
+}
@@ -1,28 +1,345 @@
 //! Input handler.
 
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, BufRead, Read};
+use std::rc::Rc;
+
+use libc;
 use rustyline;
+use rustyline::hint::{Hinter, HistoryHinter};
 use job::Job;
 
+use builtin::Registry;
+use completion::{Completer, Specs};
 use errors::{Error, Result};
+use highlight;
+use history::{self, History};
+use params::{Aliases, Parameters};
+use prompt;
 
 pub(super) struct Reader {
-    rl: rustyline::Editor<()>,
-    prompt: &'static str,
+    source: Source,
+    /// Name of the source, shown in error messages.
+    name: String,
+    /// Number of lines read so far.
+    line_number: usize,
+    /// Line number where the last job started.
+    job_line: usize,
+    /// Whether history expansion is performed, in interactive input.
+    expand_history: bool,
+}
+
+enum Source {
+    /// Line editor on the terminal.
+    Editor(Box<rustyline::Editor<Helper>>),
+    /// Lines of a script, read without prompts.
+    Script(Box<dyn BufRead>),
+}
+
+/// Helper of the line editor, which completes, highlights and hints the line.
+struct Helper {
+    completer: Completer,
+    hinter: HistoryHinter,
+    aliases: Rc<RefCell<Aliases>>,
+    builtins: Rc<Registry>,
+    /// Prompt being shown, including the non-printing characters left out of the one given to
+    /// the line editor.
+    prompt: String,
+}
+
+impl rustyline::Helper for Helper {}
+
+impl rustyline::completion::Completer for Helper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        self.completer.complete(line, pos, ctx)
+    }
+}
+
+impl rustyline::highlight::Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight::highlight(line, &self.aliases.borrow(), &self.builtins))
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        if default {
+            Cow::Borrowed(&self.prompt)
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(highlight::highlight_hint(hint))
+    }
+
+    fn highlight_char(&self, _: &str, _: usize) -> bool {
+        true
+    }
+}
+
+/// Hints are suggested from the history, to be accepted with the right arrow.
+impl Hinter for Helper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &rustyline::Context) -> Option<String> {
+        self.hinter.hint(line, pos, ctx)
+    }
+}
+
+impl rustyline::validate::Validator for Helper {}
+
+/// Stdin read byte by byte, leaving input after the current line to child processes.
+struct Stdin;
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut _, len) };
+        if n < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(n as usize)
+        }
+    }
 }
 
 impl Reader {
-    pub(super) fn new() -> Self {
+    /// Reader of stdin, with the line editor if stdin is a terminal, completing arguments by
+    /// `specs` and recognizing `aliases` and `builtins` as commands.
+    pub(super) fn new(
+        specs: Rc<RefCell<Specs>>,
+        aliases: Rc<RefCell<Aliases>>,
+        builtins: Rc<Registry>,
+    ) -> Self {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+            let config = rustyline::Config::builder()
+                .history_ignore_dups(false)
+                .max_history_size(usize::MAX)
+                .build();
+            let mut editor = rustyline::Editor::with_config(config);
+            editor.set_helper(Some(Helper {
+                completer: Completer::new(specs, Rc::clone(&aliases), Rc::clone(&builtins)),
+                hinter: HistoryHinter {},
+                aliases,
+                builtins,
+                prompt: String::new(),
+            }));
+
+            Reader {
+                source: Source::Editor(Box::new(editor)),
+                name: String::from("stdin"),
+                line_number: 0,
+                job_line: 0,
+                expand_history: true,
+            }
+        } else {
+            Self::from_script(io::BufReader::with_capacity(1, Stdin), String::from("stdin"))
+        }
+    }
+
+    /// Reader of the lines of `script`, called `name` in error messages.
+    pub(super) fn from_script<R: BufRead + 'static>(script: R, name: String) -> Self {
         Reader {
-            rl: rustyline::Editor::<()>::new(),
-            prompt: "rush $ ",
+            source: Source::Script(Box::new(script)),
+            name,
+            line_number: 0,
+            job_line: 0,
+            expand_history: false,
         }
     }
 
-    /// Read a line and parse to a job.
-    pub(super) fn read_job(&mut self) -> Result<Job> {
+    pub(super) fn is_interactive(&self) -> bool {
+        match self.source {
+            Source::Editor(_) => true,
+            Source::Script(_) => false,
+        }
+    }
+
+    /// `name:line` of the last job read from a script, for error messages.
+    pub(super) fn location(&self) -> Option<String> {
+        if self.is_interactive() {
+            None
+        } else {
+            Some(format!("{}:{}", self.name, self.job_line))
+        }
+    }
+
+    /// Read a line and parse to a job, expanding parameters with `params`.
+    /// Blank lines and comments are skipped.
+    /// `history` is used for history expansion and the prompts, and extended with the job.
+    /// If the line is incomplete, continuation lines are read until the job is complete, failing
+    /// with `Error::Incomplete` if the input ends before.
+    /// In interactive input, history expansion is performed on each line before parsing,
+    /// echoing the expanded line, and the whole job is added to the history as one entry.
+    pub(super) fn read_job(&mut self, params: &Parameters, history: &mut History) -> Result<Job> {
         use parser;
 
-        let line = self.rl.readline(self.prompt)?;
-        parser::parse_job(line.as_bytes()).map_err(Error::from)
+        loop {
+            let mut line = self.readline_with("PS1", "rush $ ", params, history)?;
+            while parser::is_empty(&line, params) {
+                line = self.readline_with("PS1", "rush $ ", params, history)?;
+            }
+            self.job_line = self.line_number;
+
+            let mut print_only = self.expand_line(&mut line, history)?;
+            let mut result = parser::parse_job(&line, params);
+            while let Err(Error::Incomplete) = result {
+                let mut next = match self.readline_with("PS2", "> ", params, history) {
+                    Err(Error::Eof) => return Err(Error::Incomplete),
+                    next => next?,
+                };
+                print_only |= self.expand_line(&mut next, history)?;
+                line.push('\n');
+                line.push_str(&next);
+                result = parser::parse_job(&line, params);
+            }
+
+            if self.is_interactive() {
+                self.add_history(&line, history);
+            }
+            if !print_only {
+                return result;
+            }
+        }
+    }
+
+    /// Perform history expansion on `line` if enabled, echoing the expanded line.
+    /// Returns whether the line is only printed, by the `p` modifier.
+    fn expand_line(&self, line: &mut String, history: &History) -> Result<bool> {
+        if !self.expand_history {
+            return Ok(false);
+        }
+        match history::expand(line, history.entries()) {
+            Ok(Some(expanded)) => {
+                eprintln!("{}", expanded.line);
+                *line = expanded.line;
+                Ok(expanded.print_only)
+            }
+            Ok(None) => Ok(false),
+            Err(e) => Err(Error::History(e)),
+        }
+    }
+
+    /// Add `line` to `history` and to the history of the line editor.
+    fn add_history(&mut self, line: &str, history: &mut History) {
+        let config = history::Config::from_env();
+        // The line editor cannot remove an entry, so its history is rebuilt if an older
+        // duplicate is erased.
+        let erased = config.erase_dups && history.entries().iter().any(|e| e == line);
+        if !history.add(line, &config) {
+            return;
+        }
+        if erased {
+            self.sync_history(history);
+        } else if let Source::Editor(ref mut rl) = self.source {
+            rl.history_mut().set_max_len(config.size.unwrap_or(usize::MAX));
+            rl.add_history_entry(line);
+        }
+    }
+
+    /// Make the history of the line editor the same as `history`, after entries are removed.
+    pub(super) fn sync_history(&mut self, history: &History) {
+        if let Source::Editor(ref mut rl) = self.source {
+            rl.clear_history();
+            for entry in history.entries() {
+                rl.add_history_entry(entry);
+            }
+        }
+    }
+
+    /// Read a line with the prompt given by the variable `ps`, or `default` if it is not set.
+    fn readline_with(
+        &mut self,
+        ps: &str,
+        default: &str,
+        params: &Parameters,
+        history: &History,
+    ) -> Result<String> {
+        let prompt = if self.is_interactive() {
+            let ps = env::var(ps).unwrap_or_else(|_| default.to_owned());
+            prompt::expand(&ps, params, history.entries().len() + 1)
+        } else {
+            String::new()
+        };
+        self.readline(&prompt)
+    }
+
+    /// Read a line without the trailing newline.
+    fn readline(&mut self, prompt: &str) -> Result<String> {
+        let mut line = match self.source {
+            Source::Editor(ref mut rl) => {
+                if let Some(helper) = rl.helper_mut() {
+                    helper.prompt = prompt::display(prompt);
+                }
+                rl.readline(&prompt::plain(prompt))?
+            }
+            Source::Script(ref mut script) => {
+                let mut line = String::new();
+                if script.read_line(&mut line)? == 0 {
+                    return Err(Error::Eof);
+                }
+                line
+            }
+        };
+        self.line_number += 1;
+
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser;
+
+    #[test]
+    fn read_job_test() {
+        let params = Parameters::default();
+        let script = io::Cursor::new("!!\nb'\n!x\n");
+        let mut reader = Reader::from_script(script, String::from("test"));
+        reader.expand_history = true;
+        let mut history = History::default();
+        history.add("echo 'a", &history::Config::from_env());
+
+        assert_eq!(
+            reader.read_job(&params, &mut history).unwrap(),
+            parser::parse_job("echo 'a\nb'", &params).unwrap()
+        );
+        assert_eq!(reader.line_number, 2);
+        match reader.read_job(&params, &mut history) {
+            Err(Error::History(_)) => {}
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn read_job_eof_test() {
+        let params = Parameters::default();
+        for script in &["echo \"abc\n", "echo a |\n", "echo a \\\n", "echo a |\n\n"] {
+            let mut reader = Reader::from_script(io::Cursor::new(*script), String::from("test"));
+            match reader.read_job(&params, &mut History::default()) {
+                Err(Error::Incomplete) => {}
+                result => panic!("{:?}: {:?}", script, result),
+            }
+        }
     }
 }
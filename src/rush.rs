@@ -1,41 +1,350 @@
 //! The Rush shell.
 
-use std::{fmt, process};
+use std::{env, fmt, fs, io, mem, process};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+use builtin::{self, Registry};
+use completion::{self, Specs};
 use reader::Reader;
 use errors::{Error, Result};
+use hash::CommandHash;
+use history::{self, History};
+use params::{self, Aliases, Parameters};
 
 pub struct Rush {
-    reader: Reader,
+    pub(super) reader: Reader,
+    pub(super) params: Parameters,
+    /// History of the interactive input, kept while files are sourced with their own readers.
+    pub(super) history: History,
+    /// Completion specs registered by `complete`, shared with the line editor.
+    pub(super) completion: Rc<RefCell<Specs>>,
+    /// Builtin commands, shared with the line editor.
+    pub(super) builtins: Rc<Registry>,
+    /// Paths of the commands run, remembered by `hash`.
+    pub(super) hash: CommandHash,
+    /// Whether `command_not_found_handle` is running, not to be run again for a command which
+    /// it fails to find.
+    pub(super) handling_not_found: bool,
+    /// Number of files being sourced.
+    source_depth: usize,
+    /// Whether `exit` has been run.
+    exiting: bool,
+    /// Whether `return` has been run in the file being sourced.
+    returning: bool,
 }
 
 impl Rush {
+    /// Create a shell reading commands from stdin, interactively if it is a terminal.
     pub fn new() -> Self {
+        Self::with_stdin(vec![])
+    }
+
+    /// Create a shell reading commands from stdin, with positional parameters `args`.
+    pub fn with_stdin(args: Vec<String>) -> Self {
+        let completion = Rc::default();
+        let aliases = Rc::default();
+        let builtins = Rc::default();
+        let mut rush = Self::with_reader(
+            Reader::new(Rc::clone(&completion), Rc::clone(&aliases), Rc::clone(&builtins)),
+            completion,
+            aliases,
+            builtins,
+            String::from("rush"),
+            args,
+        );
+
+        if rush.is_interactive() {
+            // A broken history file is not worth preventing the shell from starting.
+            let _ = rush.history.load(&history::Config::from_env());
+            rush.reader.sync_history(&rush.history);
+        }
+        rush
+    }
+
+    /// Create a shell running `command`, with `$0` set to `name` and positional parameters `args`.
+    pub fn with_command(command: &str, name: String, args: Vec<String>) -> Self {
+        let command = io::Cursor::new(command.to_owned().into_bytes());
+        Self::with_reader(
+            Reader::from_script(command, String::from("-c")),
+            Rc::default(),
+            Rc::default(),
+            Rc::default(),
+            name,
+            args,
+        )
+    }
+
+    /// Create a shell running the script at `path`, with positional parameters `args`.
+    pub fn with_script(path: &str, args: Vec<String>) -> io::Result<Self> {
+        let script = io::BufReader::new(fs::File::open(path)?);
+        Ok(Self::with_reader(
+            Reader::from_script(script, path.to_owned()),
+            Rc::default(),
+            Rc::default(),
+            Rc::default(),
+            path.to_owned(),
+            args,
+        ))
+    }
+
+    fn with_reader(
+        reader: Reader,
+        completion: Rc<RefCell<Specs>>,
+        aliases: Rc<RefCell<Aliases>>,
+        builtins: Rc<Registry>,
+        name: String,
+        args: Vec<String>,
+    ) -> Self {
         Self {
-            reader: Reader::new(),
+            reader,
+            history: History::default(),
+            params: Parameters {
+                name,
+                positional: args,
+                status: 0,
+                dir_stack: Vec::new(),
+                arrays: HashMap::new(),
+                aliases,
+            },
+            completion,
+            builtins,
+            hash: CommandHash::default(),
+            handling_not_found: false,
+            source_depth: 0,
+            exiting: false,
+            returning: false,
         }
     }
 
     /// Run read-eval-print loop.
     /// The loop is broken when reaches to EOF, or when interrupted.
-    pub fn repl(&mut self) {
-        loop {
+    /// A script also stops at a syntax error, and a sourced file at `return`.
+    /// The loop and the ones of files being sourced are broken by `exit`.
+    /// Returns the exit status of the last job, or the one given to `exit`.
+    pub fn repl(&mut self) -> i32 {
+        let interactive = self.reader.is_interactive();
+
+        while !self.exiting && !self.returning {
             match self.run() {
-                Ok(status) => println!("Exit with {}", status),
+                Ok(status) => {
+                    self.params.status = Self::status_code(status);
+                    if interactive && self.exiting {
+                        eprintln!("exit");
+                    } else if interactive {
+                        println!("Exit with {}", status);
+                    }
+                }
                 Err(Error::Eof) | Err(Error::Interrupted) => break,
-                Err(Error::IO(err)) => Self::display_error(err),
-                Err(Error::Builtin(err)) => Self::display_error(err),
-                Err(err) => eprintln!("rush: {:?}", err),
+                Err(Error::Parse(err)) => {
+                    self.display_error(&err);
+                    eprintln!("{}", err.line());
+                    eprintln!("{}", err.marker());
+
+                    self.params.status = 2;
+                    if !interactive {
+                        break;
+                    }
+                }
+                // The input ends in the middle of a job.
+                Err(err @ Error::Incomplete) => {
+                    self.display_error(&err);
+                    self.params.status = 2;
+                    break;
+                }
+                Err(err) => {
+                    self.params.status = Self::error_status(&err);
+                    self.display_error(&err);
+                    if let Error::CommandNotFound(ref name) = err {
+                        self.suggest_commands(name);
+                    }
+                }
             }
         }
+
+        if interactive {
+            if let Err(e) = self.history.save(&history::Config::from_env()) {
+                self.display_error(format!("history: {}", e));
+            }
+        }
+
+        self.params.status
     }
 
-    fn display_error<E: fmt::Display>(err: E) {
-        eprintln!("rush: {}", err);
+    /// Whether the shell reads commands from the terminal.
+    pub fn is_interactive(&self) -> bool {
+        self.reader.is_interactive()
+    }
+
+    /// Set `$PWD` to the current directory, unless it already refers to it through symbolic
+    /// links. To be called once when the shell starts.
+    pub fn init_pwd() {
+        builtin::init_pwd();
+    }
+
+    /// Run the profiles of a login shell if `profile`, and the rc files of an interactive shell
+    /// if `rc`, as listed by `startup_files`.
+    pub fn run_startup_files(&mut self, profile: bool, rc: bool) {
+        for path in startup_files(profile, rc, |name| env::var(name).ok()) {
+            self.run_startup_file(&path);
+        }
+    }
+
+    /// Run a startup file at `path` if it exists.
+    fn run_startup_file(&mut self, path: &Path) {
+        if self.exiting {
+            return;
+        }
+
+        match self.source(path, None) {
+            Ok(_) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => self.display_error(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Run commands in the file at `path` in this shell, with positional parameters replaced by
+    /// `args` if any.
+    /// Returns the exit status of the last job, or the one given to `return`.
+    pub(super) fn source(&mut self, path: &Path, args: Option<Vec<String>>) -> io::Result<i32> {
+        let file = io::BufReader::new(fs::File::open(path)?);
+        let script = Reader::from_script(file, path.display().to_string());
+
+        let reader = mem::replace(&mut self.reader, script);
+        let positional = args.map(|args| mem::replace(&mut self.params.positional, args));
+        self.source_depth += 1;
+
+        let status = self.repl();
+
+        self.source_depth -= 1;
+        self.returning = false;
+        if let Some(positional) = positional {
+            self.params.positional = positional;
+        }
+        self.reader = reader;
+        // The history may be changed by the file.
+        self.reader.sync_history(&self.history);
+
+        Ok(status)
+    }
+
+    /// Stop the shell after the current job, as `exit` does.
+    pub(super) fn exit(&mut self) {
+        self.exiting = true;
+    }
+
+    /// Stop running the file being sourced after the current job, as `return` does.
+    /// Returns false if no file is being sourced.
+    pub(super) fn return_from_source(&mut self) -> bool {
+        self.returning = self.source_depth > 0;
+        self.returning
+    }
+
+    /// Print `err`, with the location in the script if not interactive.
+    pub(super) fn display_error<E: fmt::Display>(&self, err: E) {
+        match self.reader.location() {
+            Some(location) => eprintln!("rush: {}: {}", location, err),
+            None => eprintln!("rush: {}", err),
+        }
+    }
+
+    /// Print the commands whose names are close to `name`, which is not found.
+    fn suggest_commands(&self, name: &str) {
+        let aliases = self.params.aliases.borrow();
+        let similar = completion::similar_commands(name, &aliases, &self.builtins);
+        if !similar.is_empty() {
+            eprintln!("similar commands: {}", similar.join(", "));
+        }
+    }
+
+    /// Exit status of a job which fails with `err`.
+    pub(super) fn error_status(err: &Error) -> i32 {
+        match *err {
+            Error::CommandNotFound(_) => 127,
+            Error::Exec(_, ref e) if e.kind() == io::ErrorKind::NotFound => 127,
+            Error::Exec(..) => 126,
+            _ => 1,
+        }
+    }
+
+    pub(super) fn status_code(status: process::ExitStatus) -> i32 {
+        status
+            .code()
+            .unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
     }
 
     fn run(&mut self) -> Result<process::ExitStatus> {
-        let job = self.reader.read_job()?;
-        job.run().map_err(Error::from)
+        let job = self.reader.read_job(&self.params, &mut self.history)?;
+        job.run(self)
+    }
+}
+
+/// Startup files, with the environment variables given by `var`: the profiles `/etc/profile` and
+/// `~/.rush_profile` if `profile`, then the rc files `/etc/rushrc` and the file named by `$ENV`,
+/// or `~/.rushrc` if `$ENV` is not set, if `rc`.
+fn startup_files<F: Fn(&str) -> Option<String>>(profile: bool, rc: bool, var: F) -> Vec<PathBuf> {
+    let home = var("HOME").or_else(|| params::home_dir(""));
+    let in_home = |name| home.as_ref().map(|home| Path::new(home).join(name));
+
+    let mut files = vec![];
+    if profile {
+        files.push(PathBuf::from("/etc/profile"));
+        files.extend(in_home(".rush_profile"));
+    }
+    if rc {
+        files.push(PathBuf::from("/etc/rushrc"));
+        files.extend(var("ENV").map(PathBuf::from).or_else(|| in_home(".rushrc")));
+    }
+    files
+}
+
+impl Default for Rush {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_files_test() {
+        let home = env::temp_dir().join("rush-startup-files-test");
+        let home = home.to_str().unwrap();
+        let files = |profile, rc, rc_file: Option<&str>| {
+            startup_files(profile, rc, |name| match name {
+                "HOME" => Some(home.to_owned()),
+                "ENV" => rc_file.map(str::to_owned),
+                _ => None,
+            })
+        };
+        let in_home = |name| Path::new(home).join(name);
+
+        // Login and interactive
+        assert_eq!(
+            files(true, true, None),
+            vec![
+                PathBuf::from("/etc/profile"),
+                in_home(".rush_profile"),
+                PathBuf::from("/etc/rushrc"),
+                in_home(".rushrc"),
+            ]
+        );
+        // Login and non-interactive
+        assert_eq!(
+            files(true, false, None),
+            vec![PathBuf::from("/etc/profile"), in_home(".rush_profile")]
+        );
+        // Interactive, with $ENV
+        assert_eq!(
+            files(false, true, Some("/rc")),
+            vec![PathBuf::from("/etc/rushrc"), PathBuf::from("/rc")]
+        );
+        // Non-interactive
+        assert_eq!(files(false, false, None), Vec::<PathBuf>::new());
     }
 }